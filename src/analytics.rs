@@ -0,0 +1,169 @@
+use crate::config::Config;
+use crate::model::FsLibreLine;
+use crate::resample;
+
+use chrono::{Datelike, Duration, NaiveDateTime};
+
+pub const VERY_LOW_LIMIT :u32 = 54;
+pub const LOW_LIMIT :u32 = 70;
+pub const HIGH_LIMIT :u32 = 180;
+pub const VERY_HIGH_LIMIT :u32 = 250;
+
+/// Conversion factor between the glucose units.
+pub const MG_DL_PER_MMOL_L :f64 = 18.016;
+
+/// Historic readings come every 15 minutes, a reading never stands for more time than that.
+pub const HIST_INTERVAL_MINUTES :i64 = 15;
+
+/// Shares of time (0.0 to 1.0) spent in the consensus glucose ranges.
+pub struct TimeInRange {
+    pub very_low :f64,
+    pub low :f64,
+    pub in_range :f64,
+    pub high :f64,
+    pub very_high :f64,
+}
+
+/// Splits time ordered lines into ISO weeks (Monday to Sunday).
+pub fn split_weeks(data :&[FsLibreLine]) -> Vec<&[FsLibreLine]> {
+    split_where(data, |a, b| a.timestamp.iso_week() != b.timestamp.iso_week())
+}
+
+/// Splits time ordered lines into calendar days.
+pub fn split_days(data :&[FsLibreLine]) -> Vec<&[FsLibreLine]> {
+    split_where(data, |a, b| a.timestamp.date() != b.timestamp.date())
+}
+
+/// Starts a new slice at every line for which `splits` with the first line of the current slice is true.
+fn split_where(data :&[FsLibreLine], splits :impl Fn(&FsLibreLine, &FsLibreLine) -> bool) -> Vec<&[FsLibreLine]> {
+    let mut parts = Vec::new();
+    let mut part_start = 0;
+    for index in 1..=data.len() {
+        if index == data.len() || splits(&data[part_start], &data[index]) {
+            parts.push(&data[part_start..index]);
+            part_start = index;
+        }
+    }
+    parts
+}
+
+/// Grams of carbohydrates logged in the lines.
+pub fn total_carbs(data :&[FsLibreLine]) -> u32 {
+    data.iter().map(|line| line.food_value()).sum()
+}
+
+/// All glucose readings in time order: the scanned value where the sensor was scanned,
+/// the historic value otherwise.
+pub fn glucose_series(data :&[FsLibreLine]) -> Vec<(NaiveDateTime, f64)> {
+    data.iter()
+        .map(|line| (line.timestamp, if line.gluco_scanned > 0 {line.gluco_scanned} else {line.gluco_hist}))
+        .filter(|reading| reading.1 > 0)
+        .map(|reading| (reading.0, reading.1 as f64))
+        .collect()
+}
+
+/// Returns the historic readings, each with the time it stands for: the time until the next
+/// reading, but at most the 15 minute interval so gaps without data do not count.
+pub fn weighted_hist_readings(data :&[FsLibreLine]) -> Vec<(u32, Duration)> {
+    let max_weight = Duration::minutes(HIST_INTERVAL_MINUTES);
+    let hist :Vec<&FsLibreLine> = data.iter().filter(|line| line.gluco_hist > 0).collect();
+    let mut readings = Vec::new();
+    for (index, line) in hist.iter().enumerate() {
+        let weight = match hist.get(index + 1) {
+            Some(next) => (next.timestamp - line.timestamp).min(max_weight),
+            None => max_weight,
+        };
+        readings.push((line.gluco_hist, weight));
+    }
+    readings
+}
+
+/// Shares of the consensus ranges: below 54 (level 2), 54-69 (level 1), 70-180, 181-250 (level 1)
+/// and above 250 mg/dL (level 2). Every value of `resample::statistics_series` stands for one step
+/// of the regular grid, so the shares are shares of time.
+/// Returns `None` if there are no readings.
+pub fn time_in_range(data :&[FsLibreLine], config :&Config) -> Option<TimeInRange> {
+    let values :Vec<f64> = resample::statistics_series(data, config).iter().map(|reading| reading.1).collect();
+    if values.is_empty() {
+        return None;
+    }
+    let share = |matches :&dyn Fn(f64) -> bool| values.iter().filter(|glucose| matches(**glucose)).count() as f64 / values.len() as f64;
+    Some(TimeInRange {
+        very_low: share(&|glucose| glucose < VERY_LOW_LIMIT as f64),
+        low: share(&|glucose| glucose >= VERY_LOW_LIMIT as f64 && glucose < LOW_LIMIT as f64),
+        in_range: share(&|glucose| glucose >= LOW_LIMIT as f64 && glucose <= HIGH_LIMIT as f64),
+        high: share(&|glucose| glucose > HIGH_LIMIT as f64 && glucose <= VERY_HIGH_LIMIT as f64),
+        very_high: share(&|glucose| glucose > VERY_HIGH_LIMIT as f64),
+    })
+}
+
+/// The international consensus asks for at least 14 days of data with
+/// 70% sensor coverage before GMI is reported.
+pub const GMI_MIN_DAYS :i64 = 14;
+pub const GMI_MIN_COVERAGE :f64 = 0.7;
+
+/// Glucose Management Indicator and estimated HbA1c, both in % and mmol/mol.
+pub struct GlucoseManagementIndicator {
+    pub mean_glucose :f64,
+    pub gmi_percent :f64,
+    pub gmi_mmol_mol :f64,
+    pub ea1c_percent :f64,
+    pub ea1c_mmol_mol :f64,
+    pub days :i64,
+    pub coverage :f64,
+    /// Whether `days` and `coverage` meet the consensus minimum,
+    /// otherwise the values should not be compared to a lab HbA1c.
+    pub sufficient :bool,
+}
+
+/// Share (0.0 to 1.0) of the calendar days from the first to the last line covered by historic readings.
+pub fn sensor_coverage(data :&[FsLibreLine]) -> f64 {
+    let days = period_days(data);
+    if days == 0 {
+        return 0.0;
+    }
+    let covered :i64 = weighted_hist_readings(data).iter().map(|reading| reading.1.num_seconds()).sum();
+    covered as f64 / Duration::days(days).num_seconds() as f64
+}
+
+/// Number of calendar days from the first to the last line, both included.
+pub fn period_days(data :&[FsLibreLine]) -> i64 {
+    match (data.first(), data.last()) {
+        (Some(first), Some(last)) => (last.timestamp.date() - first.timestamp.date()).num_days() + 1,
+        _ => 0,
+    }
+}
+
+/// GMI (Bergenstal et al. 2018) and the ADAG estimated A1c from the mean of `resample::statistics_series`.
+/// Returns `None` if there are no readings.
+pub fn glucose_management_indicator(data :&[FsLibreLine], config :&Config) -> Option<GlucoseManagementIndicator> {
+    let series = resample::statistics_series(data, config);
+    if series.is_empty() {
+        return None;
+    }
+    let mean_glucose = series.iter().map(|reading| reading.1).sum::<f64>() / series.len() as f64;
+    let ea1c_percent = (mean_glucose + 46.7) / 28.7;
+    let days = period_days(data);
+    let coverage = sensor_coverage(data);
+    Some(GlucoseManagementIndicator {
+        mean_glucose,
+        gmi_percent: 3.31 + 0.02392 * mean_glucose,
+        gmi_mmol_mol: 12.71 + 4.70587 * mean_glucose / MG_DL_PER_MMOL_L,
+        ea1c_percent,
+        ea1c_mmol_mol: (ea1c_percent - 2.15) * 10.929,
+        days,
+        coverage,
+        sufficient: days >= GMI_MIN_DAYS && coverage >= GMI_MIN_COVERAGE,
+    })
+}
+
+/// Percentile `p` (0.0 to 1.0) of sorted values, linearly interpolated between the closest ranks.
+pub fn percentile(sorted :&[f64], p :f64) -> f64 {
+    if sorted.len() == 1 {
+        return sorted[0];
+    }
+    let rank = p * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
@@ -0,0 +1,169 @@
+use crate::analytics;
+use crate::config::Config;
+use crate::model::FsLibreLine;
+use crate::trend;
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+
+/// Compression lows are only looked for from this hour of the evening ...
+pub const NIGHT_START_HOUR :u32 = 22;
+/// ... until this hour of the morning, when lying on the sensor is likely.
+pub const NIGHT_END_HOUR :u32 = 7;
+/// Glucose has to fall at least this much within `DROP_MINUTES` for a compression low.
+pub const COMPRESSION_DROP :f64 = 40.0;
+pub const DROP_MINUTES :i64 = 30;
+/// ... and come back to within this much of the glucose before within `RECOVERY_MINUTES`.
+pub const RECOVERY_MARGIN :f64 = 15.0;
+pub const RECOVERY_MINUTES :i64 = 90;
+/// Changes faster than this (mg/dL per minute) are not physiological.
+pub const JUMP_RATE :f64 = 5.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArtefactKind {
+    /// A sharp drop at night with a quick recovery, most likely from lying on the sensor.
+    CompressionLow,
+    /// A change between two readings faster than glucose can change.
+    Jump,
+}
+
+impl ArtefactKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArtefactKind::CompressionLow => "compression low",
+            ArtefactKind::Jump => "jump",
+        }
+    }
+}
+
+/// Readings that are probably wrong.
+pub struct Artefact {
+    pub kind :ArtefactKind,
+    pub start :NaiveDateTime,
+    pub end :NaiveDateTime,
+    /// Nadir of a compression low, reading after a jump.
+    pub glucose :f64,
+}
+
+impl Artefact {
+    pub fn contains(&self, time :NaiveDateTime) -> bool {
+        time >= self.start && time <= self.end
+    }
+}
+
+/// Compression lows and jumps in the glucose series, in time order.
+pub fn detect_artefacts(data :&[FsLibreLine], config :&Config) -> Vec<Artefact> {
+    let series = analytics::glucose_series(data);
+    let mut artefacts = compression_lows(&series, config.hypo_limit as f64);
+    for (time, _) in trend::rate_of_change(data).iter().filter(|(_, rate)| rate.abs() > JUMP_RATE) {
+        let index = series.partition_point(|reading| reading.0 < *time);
+        // the rate was taken against this reading
+        let previous = series[..index].iter()
+            .rev()
+            .find(|reading| *time - reading.0 >= Duration::minutes(trend::MIN_INTERVAL_MINUTES))
+            .map(|reading| reading.0)
+            .unwrap_or(*time);
+        // the drop into a compression low and the recovery are no jumps of their own
+        if !artefacts.iter().any(|artefact| artefact.contains(*time) || artefact.contains(previous)) {
+            artefacts.push(Artefact {kind: ArtefactKind::Jump, start: previous, end: *time, glucose: series[index].1});
+        }
+    }
+    artefacts.sort_by_key(|artefact| artefact.start);
+    artefacts
+}
+
+/// The lines without the glucose readings inside compression lows if artefacts are to be excluded.
+/// `resample::regular_series` starts from these, so the lows are left out of all statistics and the AGP.
+pub fn without_compression_lows(data :&[FsLibreLine], config :&Config) -> Vec<FsLibreLine> {
+    let mut lines = data.to_vec();
+    if config.exclude_artefacts {
+        let lows :Vec<Artefact> = detect_artefacts(data, config).into_iter()
+            .filter(|artefact| artefact.kind == ArtefactKind::CompressionLow)
+            .collect();
+        for line in lines.iter_mut().filter(|line| lows.iter().any(|low| low.contains(line.timestamp))) {
+            line.gluco_hist = 0;
+            line.gluco_scanned = 0;
+        }
+    }
+    lines
+}
+
+fn at_night(time :NaiveDateTime) -> bool {
+    time.hour() >= NIGHT_START_HOUR || time.hour() < NIGHT_END_HOUR
+}
+
+/// A reading at night followed by a drop of at least 40 mg/dL within 30 minutes to below `hypo_limit`
+/// and a return to within 15 mg/dL of it within 90 minutes.
+fn compression_lows(series :&[(NaiveDateTime, f64)], hypo_limit :f64) -> Vec<Artefact> {
+    let mut lows :Vec<Artefact> = Vec::new();
+    for (index, (time, glucose)) in series.iter().enumerate() {
+        if !at_night(*time) || lows.last().is_some_and(|low| low.end >= *time) {
+            continue;
+        }
+        let later = &series[index + 1..];
+        let drop = later.iter()
+            .take_while(|reading| reading.0 - *time <= Duration::minutes(DROP_MINUTES))
+            .position(|reading| glucose - reading.1 >= COMPRESSION_DROP);
+        let drop = match drop {
+            Some(drop) => drop,
+            None => continue,
+        };
+        let recovery = later[drop..].iter()
+            .take_while(|reading| reading.0 - *time <= Duration::minutes(RECOVERY_MINUTES))
+            .position(|reading| reading.1 >= glucose - RECOVERY_MARGIN);
+        if let Some(recovery) = recovery {
+            let dip = &later[drop..drop + recovery];
+            let nadir = dip.iter().map(|reading| reading.1).fold(f64::MAX, f64::min);
+            // a drop that stays above the limit does not fake a hypo
+            if nadir < hypo_limit {
+                lows.push(Artefact {kind: ArtefactKind::CompressionLow, start: dip[0].0, end: dip[dip.len() - 1].0, glucose: nadir});
+            }
+        }
+    }
+    lows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{episodes, resample};
+
+    /// Readings every 15 minutes from 02:00 on.
+    fn night(values :&[f64]) -> Vec<(NaiveDateTime, f64)> {
+        let start = NaiveDateTime::parse_from_str("2021.01.01 02:00", "%Y.%m.%d %H:%M").unwrap();
+        values.iter().enumerate().map(|(index, value)| (start + Duration::minutes(15 * index as i64), *value)).collect()
+    }
+
+    #[test]
+    fn sharp_dip_below_the_limit_is_a_compression_low() {
+        let lows = compression_lows(&night(&[120.0, 120.0, 60.0, 50.0, 115.0, 120.0]), 70.0);
+        assert_eq!(lows.len(), 1);
+        // the dip are the readings at 02:30 and 02:45
+        let series = night(&[0.0; 4]);
+        assert_eq!((lows[0].start, lows[0].end), (series[2].0, series[3].0));
+        assert_eq!(lows[0].glucose, 50.0);
+    }
+
+    #[test]
+    fn dip_staying_above_the_limit_is_no_compression_low() {
+        assert!(compression_lows(&night(&[160.0, 160.0, 115.0, 110.0, 155.0, 160.0]), 70.0).is_empty());
+    }
+
+    #[test]
+    fn excluded_lows_are_left_out_of_all_statistics() {
+        let data :Vec<FsLibreLine> = night(&[120.0, 120.0, 60.0, 50.0, 115.0, 120.0]).iter()
+            .map(|(time, glucose)| {
+                let mut line = FsLibreLine::new();
+                line.timestamp = *time;
+                line.gluco_hist = *glucose as u32;
+                line
+            })
+            .collect();
+        let mut config = Config::new();
+        assert_eq!(resample::statistics_series(&data, &config).len(), 6);
+        assert_eq!(episodes::hypo_episodes(&data, config.hypo_limit, &config).len(), 1);
+        config.exclude_artefacts = true;
+        assert_eq!(resample::statistics_series(&data, &config).len(), 4);
+        assert!(episodes::hypo_episodes(&data, config.hypo_limit, &config).is_empty());
+        assert_eq!(analytics::time_in_range(&data, &config).unwrap().in_range, 1.0);
+    }
+}
@@ -0,0 +1,244 @@
+use std::str::FromStr;
+use chrono::NaiveDate;
+use crate::on_board::{CarbAbsorption, CarbProfile, InsulinCurve, InsulinProfile};
+
+pub struct Config {
+    pub path :String,
+    pub gap_minutes :i64,
+    pub shade_gaps :bool,
+    pub hide_hist :bool,
+    pub hide_scans :bool,
+    pub y_min :Option<i32>,
+    pub y_max :Option<i32>,
+    pub from :Option<NaiveDate>,
+    pub to :Option<NaiveDate>,
+    pub html_report :Option<String>,
+    pub pdf_report :Option<String>,
+    pub conga_hours :i64,
+    pub risk_chart :Option<String>,
+    pub hypo_limit :u32,
+    pub hyper_limit :u32,
+    pub agp_chart :Option<String>,
+    pub overlay_chart :Option<String>,
+    pub highlight :Option<NaiveDate>,
+    pub calendar :Option<CalendarSpan>,
+    pub trend_chart :Option<String>,
+    pub trend_weekly :bool,
+    pub moving_average :usize,
+    pub show_iob :bool,
+    pub insulin_curve :InsulinCurve,
+    pub insulin_duration :i64,
+    pub insulin_peak :i64,
+    pub show_cob :bool,
+    pub carb_absorption :CarbAbsorption,
+    pub carb_duration :i64,
+    pub meal_chart :Option<String>,
+    pub overnight_chart :Option<String>,
+    pub grid_minutes :i64,
+    pub interpolation :Interpolation,
+    pub max_interpolation_gap :i64,
+    pub exclude_artefacts :bool,
+}
+
+/// Time span covered by one calendar image.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CalendarSpan {
+    Week,
+    Month,
+}
+
+impl FromStr for CalendarSpan {
+    type Err = ();
+
+    fn from_str(value :&str) -> Result<Self, Self::Err> {
+        match value {
+            "week" => Ok(CalendarSpan::Week),
+            "month" => Ok(CalendarSpan::Month),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How grid points without a reading are filled in.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    None,
+    Linear,
+    /// Linear, but only across gaps up to the maximum interpolation gap.
+    LimitedLinear,
+}
+
+impl FromStr for Interpolation {
+    type Err = ();
+
+    fn from_str(value :&str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(Interpolation::None),
+            "linear" => Ok(Interpolation::Linear),
+            "limited" => Ok(Interpolation::LimitedLinear),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            path: String::new(),
+            gap_minutes: 45,
+            shade_gaps: false,
+            hide_hist: false,
+            hide_scans: false,
+            y_min: None,
+            y_max: None,
+            from: None,
+            to: None,
+            html_report: None,
+            pdf_report: None,
+            conga_hours: 1,
+            risk_chart: None,
+            hypo_limit: 70,
+            hyper_limit: 180,
+            agp_chart: None,
+            overlay_chart: None,
+            highlight: None,
+            calendar: None,
+            trend_chart: None,
+            trend_weekly: false,
+            moving_average: 7,
+            show_iob: false,
+            insulin_curve: InsulinCurve::Exponential,
+            insulin_duration: 300,
+            insulin_peak: 75,
+            show_cob: false,
+            carb_absorption: CarbAbsorption::Bilinear,
+            carb_duration: 180,
+            meal_chart: None,
+            overnight_chart: None,
+            grid_minutes: 15,
+            interpolation: Interpolation::None,
+            max_interpolation_gap: 60,
+            exclude_artefacts: false,
+        }
+    }
+
+    pub fn insulin_profile(&self) -> InsulinProfile {
+        InsulinProfile {
+            curve: self.insulin_curve,
+            duration_minutes: self.insulin_duration,
+            peak_minutes: self.insulin_peak,
+        }
+    }
+
+    pub fn carb_profile(&self) -> CarbProfile {
+        CarbProfile {
+            absorption: self.carb_absorption,
+            duration_minutes: self.carb_duration,
+        }
+    }
+
+    /// Whether the date lies in the selected period, both ends included.
+    pub fn contains(&self, date :&NaiveDate) -> bool {
+        self.from.is_none_or(|from| *date >= from) && self.to.is_none_or(|to| *date <= to)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
+/// Reads the command line: the first plain argument is the file to open,
+/// everything starting with `--` is an option.
+pub fn parse_args(args :&[String]) -> Result<Config, String> {
+    let mut config = Config::new();
+    let mut index = 1;
+    while index < args.len() {
+        let arg = args[index].as_str();
+        match arg {
+            "--gap-minutes" => config.gap_minutes = parse_value(args, &mut index)?,
+            "--shade-gaps" => config.shade_gaps = true,
+            "--hide-hist" => config.hide_hist = true,
+            "--hide-scans" => config.hide_scans = true,
+            "--y-min" => config.y_min = Some(parse_value(args, &mut index)?),
+            "--y-max" => config.y_max = Some(parse_value(args, &mut index)?),
+            "--from" => config.from = Some(parse_value(args, &mut index)?),
+            "--to" => config.to = Some(parse_value(args, &mut index)?),
+            "--html" => config.html_report = Some(parse_value(args, &mut index)?),
+            "--pdf" => config.pdf_report = Some(parse_value(args, &mut index)?),
+            "--conga-hours" => config.conga_hours = parse_value(args, &mut index)?,
+            "--risk-chart" => config.risk_chart = Some(parse_value(args, &mut index)?),
+            "--hypo-limit" => config.hypo_limit = parse_value(args, &mut index)?,
+            "--hyper-limit" => config.hyper_limit = parse_value(args, &mut index)?,
+            "--agp" => config.agp_chart = Some(parse_value(args, &mut index)?),
+            "--overlay" => config.overlay_chart = Some(parse_value(args, &mut index)?),
+            "--highlight" => config.highlight = Some(parse_value(args, &mut index)?),
+            "--calendar" => config.calendar = Some(parse_value(args, &mut index)?),
+            "--trend-chart" => config.trend_chart = Some(parse_value(args, &mut index)?),
+            "--trend-weekly" => config.trend_weekly = true,
+            "--moving-average" => config.moving_average = parse_value(args, &mut index)?,
+            "--iob" => config.show_iob = true,
+            "--insulin-curve" => config.insulin_curve = parse_value(args, &mut index)?,
+            "--insulin-duration" => config.insulin_duration = parse_value(args, &mut index)?,
+            "--insulin-peak" => config.insulin_peak = parse_value(args, &mut index)?,
+            "--cob" => config.show_cob = true,
+            "--carb-absorption" => config.carb_absorption = parse_value(args, &mut index)?,
+            "--carb-duration" => config.carb_duration = parse_value(args, &mut index)?,
+            "--meal-chart" => config.meal_chart = Some(parse_value(args, &mut index)?),
+            "--overnight-chart" => config.overnight_chart = Some(parse_value(args, &mut index)?),
+            "--grid-minutes" => config.grid_minutes = parse_value(args, &mut index)?,
+            "--interpolation" => config.interpolation = parse_value(args, &mut index)?,
+            "--max-interpolation-gap" => config.max_interpolation_gap = parse_value(args, &mut index)?,
+            "--exclude-artefacts" => config.exclude_artefacts = true,
+            _ => {
+                if arg.starts_with("--") {
+                    return Err(format!("unknown option: '{}'", arg));
+                }
+                config.path = String::from(arg);
+            }
+        }
+        index += 1;
+    }
+    if config.path.is_empty() {
+        return Err(String::from("which file to open ???"));
+    }
+    if config.gap_minutes <= 0 {
+        return Err(String::from("--gap-minutes must be positive"));
+    }
+    if config.conga_hours <= 0 {
+        return Err(String::from("--conga-hours must be at least 1"));
+    }
+    if config.moving_average == 0 {
+        return Err(String::from("--moving-average must be at least 1"));
+    }
+    if config.insulin_duration <= 0 {
+        return Err(String::from("--insulin-duration must be positive"));
+    }
+    // only the exponential curve has a peak
+    if config.insulin_curve == InsulinCurve::Exponential && (config.insulin_peak <= 0 || 2 * config.insulin_peak >= config.insulin_duration) {
+        return Err(String::from("--insulin-peak must be positive and less than half of --insulin-duration"));
+    }
+    if config.carb_duration <= 0 {
+        return Err(String::from("--carb-duration must be positive"));
+    }
+    if config.grid_minutes != 5 && config.grid_minutes != 15 {
+        return Err(String::from("--grid-minutes must be 5 or 15"));
+    }
+    if config.max_interpolation_gap < 0 {
+        return Err(String::from("--max-interpolation-gap must not be negative"));
+    }
+    if config.y_max.unwrap_or(i32::MAX) <= config.y_min.unwrap_or(0) {
+        return Err(String::from("--y-max must be greater than --y-min"));
+    }
+    Ok(config)
+}
+
+fn parse_value<T :FromStr>(args :&[String], index :&mut usize) -> Result<T, String> {
+    let name = &args[*index];
+    *index += 1;
+    match args.get(*index) {
+        Some(value) => value.parse::<T>().map_err(|_| format!("invalid value for {}: '{}'", name, value)),
+        None => Err(format!("missing value for {}", name)),
+    }
+}
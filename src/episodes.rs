@@ -0,0 +1,257 @@
+use crate::analytics;
+use crate::config::Config;
+use crate::model::FsLibreLine;
+use crate::resample;
+
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+
+/// Consensus minimum duration of a hypo- or hyperglycemic episode.
+pub const MIN_EPISODE_MINUTES :i64 = 15;
+
+/// Episodes reaching into the time from midnight to this hour count as nocturnal.
+pub const NIGHT_END_HOUR :u32 = 6;
+
+/// A period of readings beyond a glucose threshold.
+pub struct Episode {
+    /// First reading beyond the threshold.
+    pub start :NaiveDateTime,
+    /// First reading back on the other side, or the last reading before data is missing.
+    pub end :NaiveDateTime,
+    /// Nadir for hypoglycemia, peak for hyperglycemia.
+    pub extreme :f64,
+    pub extreme_time :NaiveDateTime,
+    /// Area between the glucose curve and the threshold in mg/dL * minutes.
+    pub area :f64,
+}
+
+impl Episode {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+
+    /// Time from the nadir (or peak) until glucose is back on the other side of the threshold.
+    pub fn recovery(&self) -> Duration {
+        self.end - self.extreme_time
+    }
+
+    /// Whether the episode reaches into the night from midnight to `NIGHT_END_HOUR`.
+    pub fn nocturnal(&self) -> bool {
+        self.start.time() < NaiveTime::from_hms_opt(NIGHT_END_HOUR, 0, 0).unwrap() || self.end.date() > self.start.date()
+    }
+}
+
+/// What probably led to a hyperglycemic episode.
+pub enum HyperCause {
+    /// A meal in the hours before the episode, with the bolus given for it if any.
+    Meal {time :NaiveDateTime, carbs :u32, bolus :Option<u32>},
+    NoMeal,
+}
+
+/// Meals up to this long before a hyperglycemic episode are taken as its cause.
+pub const MEAL_LOOKBACK_HOURS :i64 = 4;
+/// A bolus this close to a meal counts as given for the meal.
+pub const MEAL_BOLUS_MINUTES :i64 = 30;
+
+/// Episodes with glucose of `resample::statistics_series` below `limit` for at least 15 minutes.
+/// Short returns above the limit of less than 15 minutes do not end an episode.
+pub fn hypo_episodes(data :&[FsLibreLine], limit :u32, config :&Config) -> Vec<Episode> {
+    detect(&resample::statistics_series(data, config), limit as f64, true)
+}
+
+/// Episodes with glucose above `limit` for at least 15 minutes, like `hypo_episodes`.
+pub fn hyper_episodes(data :&[FsLibreLine], limit :u32, config :&Config) -> Vec<Episode> {
+    detect(&resample::statistics_series(data, config), limit as f64, false)
+}
+
+/// Looks for the last meal before the episode and the bolus given for it.
+pub fn hyper_cause(data :&[FsLibreLine], episode :&Episode) -> HyperCause {
+    let lookback_start = episode.start - Duration::hours(MEAL_LOOKBACK_HOURS);
+    let meal = data.iter()
+        .rev()
+        .find(|line| line.food_value() > 0 && line.timestamp >= lookback_start && line.timestamp <= episode.start);
+    match meal {
+        Some(meal) => {
+            let bolus_window = Duration::minutes(MEAL_BOLUS_MINUTES);
+            let bolus :u32 = data.iter()
+                .filter(|line| (line.timestamp - meal.timestamp).num_seconds().abs() <= bolus_window.num_seconds())
+                .map(|line| line.fast_insulin_value())
+                .sum();
+            HyperCause::Meal {time: meal.timestamp, carbs: meal.food_value(), bolus: if bolus > 0 {Some(bolus)} else {None}}
+        }
+        None => HyperCause::NoMeal,
+    }
+}
+
+/// `below` tells whether episodes are below or above `limit`.
+fn detect(series :&[(NaiveDateTime, f64)], limit :f64, below :bool) -> Vec<Episode> {
+    // how far a reading is beyond the threshold, negative if it is not
+    let distance = |glucose :f64| if below {limit - glucose} else {glucose - limit};
+    let beyond = |glucose :f64| distance(glucose) > 0.0;
+    let more_extreme = |a :f64, b :f64| distance(a) > distance(b);
+    let min_duration = Duration::minutes(MIN_EPISODE_MINUTES);
+    // readings further apart than two historic intervals mean the sensor was off
+    let max_gap = Duration::minutes(2 * analytics::HIST_INTERVAL_MINUTES);
+    let mut episodes :Vec<Episode> = Vec::new();
+    let mut current :Option<Episode> = None;
+    let mut prev_time :Option<NaiveDateTime> = None;
+    for (time, glucose) in series {
+        if let (Some(episode), Some(prev_time)) = (current.as_mut(), prev_time) {
+            if *time - prev_time > max_gap {
+                episode.end = prev_time;
+                episodes.push(current.take().unwrap());
+            }
+        }
+        match current.as_mut() {
+            Some(episode) => {
+                if beyond(*glucose) {
+                    if more_extreme(*glucose, episode.extreme) {
+                        episode.extreme = *glucose;
+                        episode.extreme_time = *time;
+                    }
+                } else {
+                    episode.end = *time;
+                    episodes.push(current.take().unwrap());
+                }
+            }
+            None => {
+                if beyond(*glucose) {
+                    current = Some(Episode {start: *time, end: *time, extreme: *glucose, extreme_time: *time, area: 0.0});
+                }
+            }
+        }
+        prev_time = Some(*time);
+    }
+    if let (Some(mut episode), Some(prev_time)) = (current, prev_time) {
+        episode.end = prev_time;
+        episodes.push(episode);
+    }
+
+    // join episodes interrupted by less than 15 minutes, then drop the short ones
+    let mut joined :Vec<Episode> = Vec::new();
+    for episode in episodes {
+        match joined.last_mut() {
+            Some(prev) if episode.start - prev.end < min_duration => {
+                if more_extreme(episode.extreme, prev.extreme) {
+                    prev.extreme = episode.extreme;
+                    prev.extreme_time = episode.extreme_time;
+                }
+                prev.end = episode.end;
+            }
+            _ => joined.push(episode),
+        }
+    }
+    // a single reading beyond the threshold is no episode, the readings beyond have to span 15 minutes
+    joined.retain(|episode| series.iter()
+        .filter(|reading| reading.0 >= episode.start && reading.0 <= episode.end && beyond(reading.1))
+        .any(|reading| reading.0 - episode.start >= min_duration));
+
+    for episode in joined.iter_mut() {
+        // the reading before the start closes the area where the curve crosses the threshold
+        let first = series.partition_point(|reading| reading.0 < episode.start);
+        let first = if first > 0 && episode.start - series[first - 1].0 <= max_gap {first - 1} else {first};
+        let readings :Vec<&(NaiveDateTime, f64)> = series[first..].iter()
+            .take_while(|reading| reading.0 <= episode.end)
+            .collect();
+        episode.area = readings.windows(2)
+            .map(|pair| {
+                let minutes = (pair[1].0 - pair[0].0).num_seconds() as f64 / 60.0;
+                (distance(pair[0].1).max(0.0) + distance(pair[1].1).max(0.0)) / 2.0 * minutes
+            })
+            .sum();
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(readings :&[(&str, f64)]) -> Vec<(NaiveDateTime, f64)> {
+        readings.iter()
+            .map(|(time, glucose)| (NaiveDateTime::parse_from_str(time, "%Y.%m.%d %H:%M").unwrap(), *glucose))
+            .collect()
+    }
+
+    fn time(time :&str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y.%m.%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn single_low_reading_is_no_episode() {
+        let readings = series(&[("2021.01.01 10:00", 100.0), ("2021.01.01 10:15", 60.0), ("2021.01.01 10:30", 100.0)]);
+        assert!(detect(&readings, 70.0, true).is_empty());
+    }
+
+    #[test]
+    fn low_readings_over_15_minutes_are_an_episode() {
+        let readings = series(&[("2021.01.01 10:00", 100.0), ("2021.01.01 10:15", 65.0), ("2021.01.01 10:30", 55.0),
+            ("2021.01.01 10:45", 80.0)]);
+        let episodes = detect(&readings, 70.0, true);
+        assert_eq!(episodes.len(), 1);
+        let episode = &episodes[0];
+        assert_eq!((episode.start, episode.end), (time("2021.01.01 10:15"), time("2021.01.01 10:45")));
+        assert_eq!(episode.duration(), Duration::minutes(30));
+        assert_eq!((episode.extreme, episode.extreme_time), (55.0, time("2021.01.01 10:30")));
+        assert_eq!(episode.recovery(), Duration::minutes(15));
+        assert!(!episode.nocturnal());
+    }
+
+    #[test]
+    fn episode_crossing_midnight_stays_one_and_is_nocturnal() {
+        let readings = series(&[("2021.01.01 23:30", 80.0), ("2021.01.01 23:45", 60.0), ("2021.01.02 00:00", 50.0),
+            ("2021.01.02 00:15", 60.0), ("2021.01.02 00:30", 90.0)]);
+        let episodes = detect(&readings, 70.0, true);
+        assert_eq!(episodes.len(), 1);
+        assert_eq!((episodes[0].start, episodes[0].end), (time("2021.01.01 23:45"), time("2021.01.02 00:30")));
+        assert_eq!(episodes[0].extreme_time, time("2021.01.02 00:00"));
+        assert!(episodes[0].nocturnal());
+    }
+
+    #[test]
+    fn gap_in_the_data_ends_the_episode() {
+        let readings = series(&[("2021.01.01 01:00", 60.0), ("2021.01.01 01:15", 60.0), ("2021.01.01 01:30", 60.0),
+            ("2021.01.01 03:30", 60.0), ("2021.01.01 03:45", 60.0), ("2021.01.01 04:00", 100.0)]);
+        let episodes = detect(&readings, 70.0, true);
+        assert_eq!(episodes.len(), 2);
+        // the first one ends with the last reading before the gap
+        assert_eq!((episodes[0].start, episodes[0].end), (time("2021.01.01 01:00"), time("2021.01.01 01:30")));
+        assert_eq!((episodes[1].start, episodes[1].end), (time("2021.01.01 03:30"), time("2021.01.01 04:00")));
+    }
+
+    #[test]
+    fn short_return_into_range_does_not_split_an_episode() {
+        let readings = series(&[("2021.01.01 10:00", 60.0), ("2021.01.01 10:15", 60.0), ("2021.01.01 10:20", 75.0),
+            ("2021.01.01 10:30", 60.0), ("2021.01.01 10:45", 60.0), ("2021.01.01 11:00", 90.0)]);
+        let episodes = detect(&readings, 70.0, true);
+        assert_eq!(episodes.len(), 1);
+        assert_eq!((episodes[0].start, episodes[0].end), (time("2021.01.01 10:00"), time("2021.01.01 11:00")));
+    }
+
+    #[test]
+    fn single_high_reading_is_no_episode() {
+        let readings = series(&[("2021.01.01 10:00", 150.0), ("2021.01.01 10:15", 250.0), ("2021.01.01 10:30", 150.0)]);
+        assert!(detect(&readings, 180.0, false).is_empty());
+    }
+
+    #[test]
+    fn area_of_a_triangular_peak() {
+        // from 180 up to 280 and back within an hour, a triangle of 100 mg/dL * 60 minutes / 2
+        let readings = series(&[("2021.01.01 12:00", 180.0), ("2021.01.01 12:15", 230.0), ("2021.01.01 12:30", 280.0),
+            ("2021.01.01 12:45", 230.0), ("2021.01.01 13:00", 180.0), ("2021.01.01 13:15", 150.0)]);
+        let episodes = detect(&readings, 180.0, false);
+        assert_eq!(episodes.len(), 1);
+        assert_eq!((episodes[0].start, episodes[0].end), (time("2021.01.01 12:15"), time("2021.01.01 13:00")));
+        assert_eq!((episodes[0].extreme, episodes[0].extreme_time), (280.0, time("2021.01.01 12:30")));
+        assert!((episodes[0].area - 3000.0).abs() < 1e-9, "area {}", episodes[0].area);
+    }
+
+    #[test]
+    fn area_ends_at_a_gap_before_the_episode() {
+        // the reading before the start is too far away to close the area
+        let readings = series(&[("2021.01.01 12:00", 180.0), ("2021.01.01 13:00", 200.0), ("2021.01.01 13:15", 200.0),
+            ("2021.01.01 13:30", 180.0)]);
+        let episodes = detect(&readings, 180.0, false);
+        assert_eq!(episodes.len(), 1);
+        assert!((episodes[0].area - (20.0 * 15.0 + 10.0 * 15.0)).abs() < 1e-9, "area {}", episodes[0].area);
+    }
+}
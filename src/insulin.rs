@@ -0,0 +1,52 @@
+use crate::analytics;
+use crate::model::FsLibreLine;
+
+/// Units of insulin logged in a period, split into bolus (fast acting) and basal (slow acting).
+pub struct InsulinDose {
+    pub bolus :u32,
+    pub basal :u32,
+    /// Calendar days the doses were given on, to get the total daily dose of longer periods.
+    pub days :i64,
+}
+
+impl InsulinDose {
+    pub fn total(&self) -> u32 {
+        self.bolus + self.basal
+    }
+
+    /// Total dose per day, averaged over the days of the period.
+    pub fn daily_total(&self) -> f64 {
+        self.total() as f64 / self.days.max(1) as f64
+    }
+
+    /// Share (0.0 to 1.0) of basal insulin in the total dose, `None` if no insulin was logged.
+    pub fn basal_share(&self) -> Option<f64> {
+        if self.total() == 0 {
+            return None;
+        }
+        Some(self.basal as f64 / self.total() as f64)
+    }
+
+    pub fn bolus_share(&self) -> Option<f64> {
+        self.basal_share().map(|share| 1.0 - share)
+    }
+}
+
+pub fn insulin_dose(data :&[FsLibreLine]) -> InsulinDose {
+    InsulinDose {
+        bolus: data.iter().map(|line| line.fast_insulin_value()).sum(),
+        basal: data.iter().map(|line| line.slow_insulin_value()).sum(),
+        days: analytics::period_days(data),
+    }
+}
+
+/// Total daily dose with the basal/bolus split, e.g. "35 U/day: basal 15 U (43%), bolus 20 U (57%)".
+/// Basal and bolus are given per day as well for periods longer than a day.
+pub fn describe(dose :&InsulinDose) -> String {
+    let days = dose.days.max(1) as f64;
+    match (dose.basal_share(), dose.bolus_share()) {
+        (Some(basal_share), Some(bolus_share)) => format!("{:.0} U/day: basal {:.0} U ({:.0}%), bolus {:.0} U ({:.0}%)",
+            dose.daily_total(), dose.basal as f64 / days, basal_share * 100.0, dose.bolus as f64 / days, bolus_share * 100.0),
+        _ => String::from("no insulin logged"),
+    }
+}
@@ -0,0 +1,25 @@
+pub mod analytics;
+pub mod artefacts;
+pub mod config;
+pub mod episodes;
+pub mod insulin;
+pub mod meals;
+pub mod model;
+pub mod on_board;
+pub mod parse;
+pub mod overnight;
+pub mod pdf;
+pub mod plot;
+pub mod plot_agp;
+pub mod plot_calendar;
+pub mod plot_meals;
+pub mod plot_overlay;
+pub mod plot_overnight;
+pub mod plot_trends;
+pub mod ratios;
+pub mod report;
+pub mod resample;
+pub mod risk;
+pub mod sessions;
+pub mod trend;
+pub mod variability;
@@ -1,21 +1,21 @@
-mod model;
-mod parse;
-mod plot;
-
-use crate::model::FsLibreLine;
+use fslibre_plot::{config, parse, plot, plot_agp, plot_calendar, plot_meals, plot_overlay, plot_overnight, plot_trends, report};
+use fslibre_plot::config::{CalendarSpan, Config};
+use fslibre_plot::model::FsLibreLine;
 
 use std::env;
 use std::fs::File;
-use chrono::{Datelike, NaiveDateTime};
+use chrono::{NaiveDate, NaiveDateTime};
 use std::ops::Add;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("which file to open ???");
+    let config_result = config::parse_args(&args);
+    if config_result.is_err() {
+        println!("{}", config_result.err().unwrap());
         return;
     }
-    let path = &args[1];
+    let config = config_result.unwrap();
+    let path = &config.path;
     let file_result = File::open(&path);
     if file_result.is_err() {
         println!("could not open file: '{}'!!!", &path);
@@ -30,33 +30,131 @@ fn main() {
     data.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
     println!("unifying timestamps");
     data = unify_timestamps(&data);
+    data.retain(|line| config.contains(&line.timestamp.date()));
+    let period = data.clone();
+
+    let days = split_days(data);
+    for data_of_day in &days {
+        let earlier = &period[..period.partition_point(|line| line.timestamp < data_of_day[0].timestamp)];
+        plot_wrapper(data_of_day, earlier, &config);
+    }
+
+    if let Some(span) = config.calendar {
+        for (first, last) in plot_calendar::calendar_pages(&days, span) {
+            let path = build_calendar_path(first, span);
+            println!("creating file {}", path);
+            let plot_result = plot_calendar::plot_calendar(&days, first, last, path.as_str(), &config);
+            if plot_result.is_err() {
+                println!("error creating plot!!!");
+                println!("{}", plot_result.err().unwrap());
+            }
+        }
+    }
 
-    let mut data_of_day = Vec::new();
+    report::print_period(&period, &config);
+    if let Some(chart_path) = &config.agp_chart {
+        println!("creating file {}", chart_path);
+        let plot_result = plot_agp::plot_agp(&period, chart_path, &config);
+        if plot_result.is_err() {
+            println!("error creating plot!!!");
+            println!("{}", plot_result.err().unwrap());
+        }
+    }
+    if let Some(chart_path) = &config.overlay_chart {
+        if let Some(highlight) = config.highlight {
+            if !days.iter().any(|data_of_day| data_of_day[0].timestamp.date() == highlight) {
+                println!("no data on {} to highlight", highlight.format("%Y-%m-%d"));
+            }
+        }
+        println!("creating file {}", chart_path);
+        let plot_result = plot_overlay::plot_overlay(&days, chart_path, config.highlight, &config);
+        if plot_result.is_err() {
+            println!("error creating plot!!!");
+            println!("{}", plot_result.err().unwrap());
+        }
+    }
+    if let Some(chart_path) = &config.risk_chart {
+        println!("creating file {}", chart_path);
+        let plot_result = plot_trends::plot_risk_trend(&period, chart_path, &config);
+        if plot_result.is_err() {
+            println!("error creating plot!!!");
+            println!("{}", plot_result.err().unwrap());
+        }
+    }
+    if let Some(chart_path) = &config.trend_chart {
+        println!("creating file {}", chart_path);
+        let plot_result = plot_trends::plot_statistics_trend(&period, chart_path, config.trend_weekly, config.moving_average, &config);
+        if plot_result.is_err() {
+            println!("error creating plot!!!");
+            println!("{}", plot_result.err().unwrap());
+        }
+    }
+    if let Some(chart_path) = &config.meal_chart {
+        println!("creating file {}", chart_path);
+        let plot_result = plot_meals::plot_meal_responses(&period, chart_path, &config);
+        if plot_result.is_err() {
+            println!("error creating plot!!!");
+            println!("{}", plot_result.err().unwrap());
+        }
+    }
+    if let Some(chart_path) = &config.overnight_chart {
+        println!("creating file {}", chart_path);
+        let plot_result = plot_overnight::plot_overnight(&period, chart_path, &config);
+        if plot_result.is_err() {
+            println!("error creating plot!!!");
+            println!("{}", plot_result.err().unwrap());
+        }
+    }
+    if let Some(html_path) = &config.html_report {
+        println!("creating file {}", html_path);
+        let report_result = report::write_html(html_path, &period, &days, &config);
+        if report_result.is_err() {
+            println!("error creating report!!!");
+            println!("{}", report_result.err().unwrap());
+        }
+    }
+    if let Some(pdf_path) = &config.pdf_report {
+        println!("creating file {}", pdf_path);
+        let report_result = report::write_pdf(pdf_path, &period, &days, &config);
+        if report_result.is_err() {
+            println!("error creating report!!!");
+            println!("{}", report_result.err().unwrap());
+        }
+    }
+}
+
+fn split_days(data :Vec<FsLibreLine>) -> Vec<Vec<FsLibreLine>> {
+    let mut days = Vec::new();
+    let mut data_of_day :Vec<FsLibreLine> = Vec::new();
     for line in data {
         if data_of_day.is_empty() {
             data_of_day.push(line);
         } else {
             let prev_line = data_of_day.get(data_of_day.len() - 1);
-            if prev_line.unwrap().timestamp.day() == line.timestamp.day() {
+            if prev_line.unwrap().timestamp.date() == line.timestamp.date() {
                 data_of_day.push(line);
             } else {
-                plot_wrapper(&data_of_day);
-                data_of_day.clear();
+                days.push(data_of_day);
+                data_of_day = Vec::new();
                 data_of_day.push(line);
             }
         }
     }
-    // plot last day
-    plot_wrapper(&data_of_day);
+    // last day
+    if !data_of_day.is_empty() {
+        days.push(data_of_day);
+    }
+    days
 }
 
-fn plot_wrapper(data_of_day :&Vec<FsLibreLine>) {
+fn plot_wrapper(data_of_day :&Vec<FsLibreLine>, earlier :&[FsLibreLine], config :&Config) {
     let title_result = build_title(&data_of_day);
     if title_result.is_ok() {
         let title = title_result.unwrap();
         let path = build_path(&title);
+        report::print_day(&title, data_of_day, config);
         println!("creating file {}", path);
-        let plot_result = plot::plot(&data_of_day, path.as_str(), title.as_str());
+        let plot_result = plot::plot(&data_of_day, earlier, path.as_str(), title.as_str(), config);
         if plot_result.is_err() {
             println!("error creating plot!!!");
             println!("{}", plot_result.err().unwrap());
@@ -80,27 +178,34 @@ fn build_path(title :&str) -> String {
     path.add(".png")
 }
 
+fn build_calendar_path(first :NaiveDate, span :CalendarSpan) -> String {
+    match span {
+        CalendarSpan::Week => format!("week-{}.png", first.format("%G-W%V")),
+        CalendarSpan::Month => format!("month-{}.png", first.format("%Y-%m")),
+    }
+}
+
+/// Merges lines sharing a timestamp (e.g. a bolus logged together with a meal) into one line,
+/// so every event is drawn exactly once.
 fn unify_timestamps(data :&Vec<FsLibreLine>) -> Vec<FsLibreLine> {
-    let mut unified = Vec::new();
-    let mut prev_line :&FsLibreLine = &FsLibreLine::new();
+    let mut unified :Vec<FsLibreLine> = Vec::new();
     for line in data {
-        if line.timestamp == prev_line.timestamp {
-            let mut unified_line = FsLibreLine::new();
-            unified_line.timestamp = line.timestamp;
-            unified_line.fast_insulin = if line.fast_insulin > 0 {line.fast_insulin} else {prev_line.fast_insulin};
-            unified_line.fast_insulin_units = if line.fast_insulin_units > 0 {line.fast_insulin_units} else {prev_line.fast_insulin_units};
-            unified_line.fast_insulin_non_numeric = if line.fast_insulin_non_numeric > 0 {line.fast_insulin_non_numeric} else {prev_line.fast_insulin_non_numeric};
-            unified_line.food = if line.food > 0 {line.food} else {prev_line.food};
-            unified_line.food_non_numeric = if line.food_non_numeric > 0 {line.food_non_numeric} else {prev_line.food_non_numeric};
-            unified_line.carbohydrate = if line.carbohydrate > 0 {line.carbohydrate} else {prev_line.carbohydrate};
-            unified_line.slow_insulin = if line.slow_insulin > 0 {line.slow_insulin} else {prev_line.slow_insulin};
-            unified_line.slow_insulin_units = if line.slow_insulin_units > 0 {line.slow_insulin_units} else {prev_line.slow_insulin_units};
-            unified_line.slow_insulin_non_numeric = if line.slow_insulin_non_numeric > 0 {line.slow_insulin_non_numeric} else {prev_line.slow_insulin_non_numeric};
-            unified.push(unified_line);
-        } else {
-            unified.push(line.clone());
+        match unified.last_mut() {
+            Some(prev_line) if line.timestamp == prev_line.timestamp => {
+                prev_line.gluco_hist = if line.gluco_hist > 0 {line.gluco_hist} else {prev_line.gluco_hist};
+                prev_line.gluco_scanned = if line.gluco_scanned > 0 {line.gluco_scanned} else {prev_line.gluco_scanned};
+                prev_line.fast_insulin = if line.fast_insulin > 0 {line.fast_insulin} else {prev_line.fast_insulin};
+                prev_line.fast_insulin_units = if line.fast_insulin_units > 0 {line.fast_insulin_units} else {prev_line.fast_insulin_units};
+                prev_line.fast_insulin_non_numeric = if line.fast_insulin_non_numeric > 0 {line.fast_insulin_non_numeric} else {prev_line.fast_insulin_non_numeric};
+                prev_line.food = if line.food > 0 {line.food} else {prev_line.food};
+                prev_line.food_non_numeric = if line.food_non_numeric > 0 {line.food_non_numeric} else {prev_line.food_non_numeric};
+                prev_line.carbohydrate = if line.carbohydrate > 0 {line.carbohydrate} else {prev_line.carbohydrate};
+                prev_line.slow_insulin = if line.slow_insulin > 0 {line.slow_insulin} else {prev_line.slow_insulin};
+                prev_line.slow_insulin_units = if line.slow_insulin_units > 0 {line.slow_insulin_units} else {prev_line.slow_insulin_units};
+                prev_line.slow_insulin_non_numeric = if line.slow_insulin_non_numeric > 0 {line.slow_insulin_non_numeric} else {prev_line.slow_insulin_non_numeric};
+            }
+            _ => unified.push(line.clone()),
         }
-        prev_line = line;
     }
     unified
 }
@@ -0,0 +1,106 @@
+use crate::analytics;
+use crate::config::Config;
+use crate::model::FsLibreLine;
+use crate::resample;
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+
+/// Glucose is followed for this long after a meal.
+pub const RESPONSE_HOURS :i64 = 3;
+
+/// The reading the response is measured against must be at most this long before the meal.
+const BASELINE_MINUTES :i64 = 15;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MealSlot {
+    Breakfast,
+    Lunch,
+    Dinner,
+}
+
+impl MealSlot {
+    pub const ALL :[MealSlot; 3] = [MealSlot::Breakfast, MealSlot::Lunch, MealSlot::Dinner];
+
+    /// Meals before 11:00 count as breakfast, before 16:00 as lunch, later ones as dinner.
+    pub fn of(time :NaiveDateTime) -> MealSlot {
+        match time.hour() {
+            0..=10 => MealSlot::Breakfast,
+            11..=15 => MealSlot::Lunch,
+            _ => MealSlot::Dinner,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MealSlot::Breakfast => "breakfast",
+            MealSlot::Lunch => "lunch",
+            MealSlot::Dinner => "dinner",
+        }
+    }
+}
+
+/// Glucose in the hours after a meal.
+pub struct MealResponse {
+    pub time :NaiveDateTime,
+    pub carbs :u32,
+    pub slot :MealSlot,
+    /// Last reading before the meal.
+    pub baseline :f64,
+    pub peak :f64,
+    pub time_to_peak :Duration,
+    /// Rise from the baseline to the peak.
+    pub delta :f64,
+    /// Incremental area under the curve: area above the baseline in mg/dL * minutes.
+    pub iauc :f64,
+    /// Readings from the meal on, with the time since the meal.
+    pub trace :Vec<(Duration, f64)>,
+}
+
+/// The response to every meal with a reading shortly before it and readings over most of the
+/// following hours; meals without (e.g. while the sensor was off) are left out.
+/// Glucose is taken from `resample::statistics_series`.
+pub fn meal_responses(data :&[FsLibreLine], config :&Config) -> Vec<MealResponse> {
+    let series = resample::statistics_series(data, config);
+    let response_end = Duration::hours(RESPONSE_HOURS);
+    let mut responses = Vec::new();
+    for meal in data.iter().filter(|line| line.food_value() > 0) {
+        let baseline = series.iter()
+            .rev()
+            .find(|reading| reading.0 <= meal.timestamp && meal.timestamp - reading.0 <= Duration::minutes(BASELINE_MINUTES));
+        let baseline = match baseline {
+            Some(reading) => reading.1,
+            None => continue,
+        };
+        let mut trace :Vec<(Duration, f64)> = series.iter()
+            .filter(|reading| reading.0 >= meal.timestamp && reading.0 - meal.timestamp <= response_end)
+            .map(|reading| (reading.0 - meal.timestamp, reading.1))
+            .collect();
+        trace.insert(0, (Duration::zero(), baseline));
+        // a trace ending too early or with gaps in it would understate the response
+        let covered = trace.last().map(|reading| reading.0).unwrap_or_else(Duration::zero) >= response_end - Duration::minutes(2 * analytics::HIST_INTERVAL_MINUTES);
+        let gapless = trace.windows(2).all(|pair| pair[1].0 - pair[0].0 <= Duration::minutes(2 * analytics::HIST_INTERVAL_MINUTES));
+        if !covered || !gapless {
+            continue;
+        }
+        let (time_to_peak, peak) = trace.iter()
+            .fold((Duration::zero(), baseline), |max, reading| if reading.1 > max.1 {*reading} else {max});
+        let iauc = trace.windows(2)
+            .map(|pair| {
+                let minutes = (pair[1].0 - pair[0].0).num_seconds() as f64 / 60.0;
+                ((pair[0].1 - baseline).max(0.0) + (pair[1].1 - baseline).max(0.0)) / 2.0 * minutes
+            })
+            .sum();
+        responses.push(MealResponse {
+            time: meal.timestamp,
+            carbs: meal.food_value(),
+            slot: MealSlot::of(meal.timestamp),
+            baseline,
+            peak,
+            time_to_peak,
+            delta: peak - baseline,
+            iauc,
+            trace,
+        });
+    }
+    responses
+}
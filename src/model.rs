@@ -40,3 +40,28 @@ impl FsLibreLine {
         }
     }
 }
+
+impl FsLibreLine {
+    /// Units of fast acting insulin, whichever column they were exported to.
+    pub fn fast_insulin_value(&self) -> u32 {
+        if self.fast_insulin_units > 0 {self.fast_insulin_units} else if self.fast_insulin > 0 {self.fast_insulin} else {self.fast_insulin_non_numeric}
+    }
+
+    /// Units of slow acting insulin, whichever column they were exported to.
+    /// The reader export has fewer columns between the scan glucose and the long acting insulin
+    /// than the fields here, its long acting units are read into `carbohydrate`.
+    pub fn slow_insulin_value(&self) -> u32 {
+        if self.slow_insulin_units > 0 {self.slow_insulin_units} else if self.slow_insulin > 0 {self.slow_insulin} else if self.slow_insulin_non_numeric > 0 {self.slow_insulin_non_numeric} else {self.carbohydrate}
+    }
+
+    /// Grams of carbohydrates eaten, read into `food` from the reader export.
+    pub fn food_value(&self) -> u32 {
+        if self.food > 0 {self.food} else if self.food_non_numeric > 0 {self.food_non_numeric} else {0}
+    }
+}
+
+impl Default for FsLibreLine {
+    fn default() -> Self {
+        FsLibreLine::new()
+    }
+}
@@ -0,0 +1,118 @@
+use crate::model::FsLibreLine;
+
+use std::str::FromStr;
+use chrono::NaiveDateTime;
+
+/// Shape of the insulin action curve.
+#[derive(Clone, Copy, PartialEq)]
+pub enum InsulinCurve {
+    Linear,
+    Exponential,
+}
+
+impl FromStr for InsulinCurve {
+    type Err = ();
+
+    fn from_str(value :&str) -> Result<Self, Self::Err> {
+        match value {
+            "linear" => Ok(InsulinCurve::Linear),
+            "exponential" => Ok(InsulinCurve::Exponential),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Shape of the carbohydrate absorption over time.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CarbAbsorption {
+    Linear,
+    Bilinear,
+}
+
+impl FromStr for CarbAbsorption {
+    type Err = ();
+
+    fn from_str(value :&str) -> Result<Self, Self::Err> {
+        match value {
+            "linear" => Ok(CarbAbsorption::Linear),
+            "bilinear" => Ok(CarbAbsorption::Bilinear),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How fast acting insulin works after it was given.
+pub struct InsulinProfile {
+    pub curve :InsulinCurve,
+    /// Time until the dose has no effect left.
+    pub duration_minutes :i64,
+    /// Time of the highest activity, only used by the exponential curve.
+    pub peak_minutes :i64,
+}
+
+impl InsulinProfile {
+    /// Share (0.0 to 1.0) of a dose still active `minutes` after it was given.
+    pub fn remaining(&self, minutes :f64) -> f64 {
+        let duration = self.duration_minutes as f64;
+        if minutes <= 0.0 {
+            return 1.0;
+        }
+        if minutes >= duration {
+            return 0.0;
+        }
+        match self.curve {
+            InsulinCurve::Linear => 1.0 - minutes / duration,
+            InsulinCurve::Exponential => {
+                // exponential model of rapid acting analogs as used by Loop and oref0
+                let peak = self.peak_minutes as f64;
+                let tau = peak * (1.0 - peak / duration) / (1.0 - 2.0 * peak / duration);
+                let a = 2.0 * tau / duration;
+                let s = 1.0 / (1.0 - a + (1.0 + a) * (-duration / tau).exp());
+                1.0 - s * (1.0 - a) * ((minutes * minutes / (tau * duration * (1.0 - a)) - minutes / tau - 1.0) * (-minutes / tau).exp() + 1.0)
+            }
+        }
+    }
+}
+
+/// How fast carbohydrates are absorbed after a meal.
+pub struct CarbProfile {
+    pub absorption :CarbAbsorption,
+    /// Time until a meal is absorbed completely.
+    pub duration_minutes :i64,
+}
+
+impl CarbProfile {
+    /// Share (0.0 to 1.0) of a meal not yet absorbed `minutes` after it was eaten.
+    pub fn remaining(&self, minutes :f64) -> f64 {
+        let duration = self.duration_minutes as f64;
+        if minutes <= 0.0 {
+            return 1.0;
+        }
+        if minutes >= duration {
+            return 0.0;
+        }
+        let progress = minutes / duration;
+        match self.absorption {
+            CarbAbsorption::Linear => 1.0 - progress,
+            // the absorption rate rises linearly up to half of the duration and falls back to zero after
+            CarbAbsorption::Bilinear if progress < 0.5 => 1.0 - 2.0 * progress * progress,
+            CarbAbsorption::Bilinear => 2.0 * (1.0 - progress) * (1.0 - progress),
+        }
+    }
+}
+
+/// Units of fast acting insulin still active at `time`, from all doses given before.
+pub fn insulin_on_board(data :&[FsLibreLine], profile :&InsulinProfile, time :NaiveDateTime) -> f64 {
+    data.iter()
+        .filter(|line| line.fast_insulin_value() > 0 && line.timestamp <= time)
+        .map(|line| line.fast_insulin_value() as f64 * profile.remaining((time - line.timestamp).num_seconds() as f64 / 60.0))
+        .sum()
+}
+
+/// Grams of carbohydrates not yet absorbed at `time`, from all meals eaten before.
+pub fn carbs_on_board(data :&[FsLibreLine], profile :&CarbProfile, time :NaiveDateTime) -> f64 {
+    data.iter()
+        .filter(|line| line.food_value() > 0 && line.timestamp <= time)
+        .map(|line| line.food_value() as f64 * profile.remaining((time - line.timestamp).num_seconds() as f64 / 60.0))
+        .sum()
+}
@@ -0,0 +1,134 @@
+use crate::analytics;
+use crate::config::Config;
+use crate::model::FsLibreLine;
+use crate::resample;
+use crate::variability;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+/// The night is analysed from midnight until this hour.
+pub const NIGHT_END_HOUR :u32 = 7;
+/// Food or fast acting insulin this many hours before midnight or during the night disturb the night.
+pub const UNDISTURBED_HOURS :i64 = 4;
+/// Glucose staying within this change over the night shows the slow insulin matches the needs.
+pub const STABLE_CHANGE :f64 = 30.0;
+/// A rise of more than this from the nadir in the early night to the end of the night counts as dawn phenomenon.
+pub const DAWN_RISE :f64 = 20.0;
+
+/// Glucose during a night without food and without fast acting insulin.
+pub struct Night {
+    pub date :NaiveDate,
+    pub start :f64,
+    pub end :f64,
+    /// Change per hour of the least squares line through the readings.
+    pub slope :f64,
+    /// Rise from the lowest reading between 02:00 and 05:00 to the end of the night.
+    pub dawn_rise :f64,
+    /// Readings with the time since midnight.
+    pub trace :Vec<(Duration, f64)>,
+}
+
+impl Night {
+    pub fn change(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+/// What the nights say about the slow acting insulin.
+#[derive(Clone, Copy, PartialEq)]
+pub enum BasalAssessment {
+    /// Glucose stays within 30 mg/dL over the night.
+    Adequate,
+    /// Glucose falls by more than 30 mg/dL.
+    TooHigh,
+    /// Glucose rises by more than 30 mg/dL over the whole night.
+    TooLow,
+    /// Glucose rises by more than 30 mg/dL, mostly in the early morning.
+    DawnPhenomenon,
+}
+
+impl BasalAssessment {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            BasalAssessment::Adequate => "glucose stays stable over night, the slow insulin appears adequate",
+            BasalAssessment::TooHigh => "glucose falls over night, the slow insulin appears too high",
+            BasalAssessment::TooLow => "glucose rises over night, the slow insulin appears too low",
+            BasalAssessment::DawnPhenomenon => "glucose rises in the early morning (dawn phenomenon), the slow insulin may not last or cover the morning",
+        }
+    }
+}
+
+/// The nights of the period with readings all through and no food or fast acting insulin
+/// from 4 hours before midnight until the end of the night, glucose from `resample::statistics_series`.
+pub fn undisturbed_nights(data :&[FsLibreLine], config :&Config) -> Vec<Night> {
+    let series = resample::statistics_series(data, config);
+    let max_gap = Duration::minutes(2 * analytics::HIST_INTERVAL_MINUTES);
+    let mut nights = Vec::new();
+    for day in analytics::split_days(data) {
+        let midnight = day[0].timestamp.date().and_time(NaiveTime::from_hms(0, 0, 0));
+        let night_end = midnight + Duration::hours(NIGHT_END_HOUR as i64);
+        let disturbed = data.iter()
+            .any(|line| line.timestamp >= midnight - Duration::hours(UNDISTURBED_HOURS) && line.timestamp <= night_end
+                && (line.food_value() > 0 || line.fast_insulin_value() > 0));
+        if disturbed {
+            continue;
+        }
+        let readings :Vec<(NaiveDateTime, f64)> = series.iter()
+            .filter(|reading| reading.0 >= midnight && reading.0 <= night_end)
+            .cloned()
+            .collect();
+        let complete = readings.len() >= 2
+            && readings[0].0 - midnight <= max_gap
+            && night_end - readings[readings.len() - 1].0 <= max_gap
+            && readings.windows(2).all(|pair| pair[1].0 - pair[0].0 <= max_gap);
+        if !complete {
+            continue;
+        }
+        let trace :Vec<(Duration, f64)> = readings.iter().map(|reading| (reading.0 - midnight, reading.1)).collect();
+        let end = trace[trace.len() - 1].1;
+        let nadir = trace.iter()
+            .filter(|reading| reading.0 >= Duration::hours(2) && reading.0 <= Duration::hours(5))
+            .map(|reading| reading.1)
+            .fold(end, f64::min);
+        nights.push(Night {
+            date: midnight.date(),
+            start: trace[0].1,
+            end,
+            slope: slope(&trace),
+            dawn_rise: end - nadir,
+            trace,
+        });
+    }
+    nights
+}
+
+/// Judges the slow insulin by the mean change over the nights, `None` without nights.
+pub fn assess_basal(nights :&[Night]) -> Option<BasalAssessment> {
+    if nights.is_empty() {
+        return None;
+    }
+    let change = variability::mean(&nights.iter().map(|night| night.change()).collect::<Vec<f64>>());
+    let dawn_rise = variability::mean(&nights.iter().map(|night| night.dawn_rise).collect::<Vec<f64>>());
+    Some(if change < -STABLE_CHANGE {
+        BasalAssessment::TooHigh
+    } else if change <= STABLE_CHANGE {
+        BasalAssessment::Adequate
+    } else if dawn_rise > DAWN_RISE && dawn_rise >= change / 2.0 {
+        BasalAssessment::DawnPhenomenon
+    } else {
+        BasalAssessment::TooLow
+    })
+}
+
+/// Least squares slope in mg/dL per hour.
+fn slope(trace :&[(Duration, f64)]) -> f64 {
+    let hours :Vec<f64> = trace.iter().map(|reading| reading.0.num_seconds() as f64 / 3600.0).collect();
+    let values :Vec<f64> = trace.iter().map(|reading| reading.1).collect();
+    let (mean_hours, mean_value) = (variability::mean(&hours), variability::mean(&values));
+    let covariance :f64 = hours.iter().zip(values.iter()).map(|(hour, value)| (hour - mean_hours) * (value - mean_value)).sum();
+    let variance :f64 = hours.iter().map(|hour| (hour - mean_hours) * (hour - mean_hours)).sum();
+    if variance == 0.0 {
+        return 0.0;
+    }
+    covariance / variance
+}
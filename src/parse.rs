@@ -303,3 +303,55 @@ pub fn debug_print_file(data :&Vec<FsLibreLine>) {
         println!("could not create debug file: {}", create_result.err().unwrap());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Columns of the reader export: ID, time, record type, historic glucose, scan glucose,
+    /// non numeric rapid acting insulin, rapid acting units, non numeric food, carbs (g),
+    /// non numeric long acting insulin, long acting units, notes and the strip, ketone and insulin columns.
+    fn export_line(fields :&[&str]) -> String {
+        let mut columns :Vec<&str> = fields.to_vec();
+        columns.resize(19, "");
+        columns.join(SEPARATOR) + SEPARATOR
+    }
+
+    fn parse(fields :&[&str]) -> FsLibreLine {
+        let (line, error) = parse_line(&export_line(fields), &3);
+        assert!(!error);
+        line
+    }
+
+    #[test]
+    fn long_acting_insulin_of_export() {
+        let line = parse(&["1042", "2021.01.05 22:03", "4", "", "", "", "", "", "", "", "18"]);
+        assert_eq!(line.slow_insulin_value(), 18);
+        assert_eq!(line.fast_insulin_value(), 0);
+        assert_eq!(line.food_value(), 0);
+    }
+
+    #[test]
+    fn rapid_acting_insulin_of_export() {
+        let line = parse(&["1043", "2021.01.05 07:32", "4", "", "", "", "5"]);
+        assert_eq!(line.fast_insulin_value(), 5);
+        assert_eq!(line.slow_insulin_value(), 0);
+    }
+
+    #[test]
+    fn carbs_of_export() {
+        let line = parse(&["1044", "2021.01.05 07:32", "5", "", "", "", "", "", "52"]);
+        assert_eq!(line.food_value(), 52);
+        assert_eq!(line.slow_insulin_value(), 0);
+        assert_eq!(line.fast_insulin_value(), 0);
+    }
+
+    #[test]
+    fn glucose_of_export() {
+        let line = parse(&["1045", "2021.01.05 07:45", "0", "143"]);
+        assert_eq!(line.gluco_hist, 143);
+        assert_eq!(line.timestamp, NaiveDateTime::parse_from_str("2021.01.05 07:45", TIMESTAMP_FORMAT).unwrap());
+        let line = parse(&["1046", "2021.01.05 07:49", "1", "", "151"]);
+        assert_eq!(line.gluco_scanned, 151);
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs;
+
+/// A4 in PDF points.
+const PAGE_WIDTH :f64 = 595.0;
+const PAGE_HEIGHT :f64 = 842.0;
+const MARGIN :f64 = 40.0;
+const FONT_SIZE :f64 = 9.0;
+const LINE_HEIGHT :f64 = 11.0;
+/// Courier glyphs are 0.6 of the font size wide.
+const LINE_CHARS :usize = ((PAGE_WIDTH - 2.0 * MARGIN) / (0.6 * FONT_SIZE)) as usize;
+const PAGE_LINES :usize = ((PAGE_HEIGHT - 2.0 * MARGIN) / LINE_HEIGHT) as usize;
+
+/// Writes the lines as a plain text PDF document in a monospaced font, so columns stay aligned.
+/// The document is plain ASCII, other characters are written as escaped WinAnsi codes.
+/// Long lines are wrapped, a new page starts when one is full.
+pub fn write_text(path :&str, lines :&[String]) -> std::io::Result<()> {
+    let wrapped :Vec<String> = lines.iter().flat_map(|line| wrap(line)).collect();
+    let pages :Vec<&[String]> = if wrapped.is_empty() {vec![&[]]} else {wrapped.chunks(PAGE_LINES).collect()};
+
+    // objects 1 to 3 are the catalog, the page tree and the font, every page adds itself and its content
+    let mut objects = vec![
+        String::from("<< /Type /Catalog /Pages 2 0 R >>"),
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len()).map(|index| format!("{} 0 R", 4 + 2 * index)).collect::<Vec<String>>().join(" "), pages.len()),
+        String::from("<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"),
+    ];
+    for (index, page) in pages.iter().enumerate() {
+        objects.push(format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH, PAGE_HEIGHT, 5 + 2 * index));
+        let mut content = format!("BT /F1 {} Tf {} TL {} {} Td\n", FONT_SIZE, LINE_HEIGHT, MARGIN, PAGE_HEIGHT - MARGIN - FONT_SIZE);
+        for line in page.iter() {
+            content.push_str(&format!("({}) Tj T*\n", escape(line)));
+        }
+        content.push_str("ET");
+        objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", index + 1, object));
+    }
+    let xref_offset = pdf.len();
+    pdf.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref_offset));
+    fs::write(path, pdf)
+}
+
+/// Splits a line into pieces fitting the page width, continued lines are indented.
+fn wrap(line :&str) -> Vec<String> {
+    let chars :Vec<char> = line.chars().collect();
+    let mut pieces = vec![chars.iter().take(LINE_CHARS).collect::<String>()];
+    let mut rest = chars.get(LINE_CHARS..).unwrap_or(&[]);
+    while !rest.is_empty() {
+        let length = rest.len().min(LINE_CHARS - 4);
+        pieces.push(format!("    {}", rest[..length].iter().collect::<String>()));
+        rest = &rest[length..];
+    }
+    pieces
+}
+
+/// Escapes the characters with a meaning in PDF strings and encodes the others as WinAnsi,
+/// the encoding of the standard font. Characters WinAnsi does not have become '?'.
+fn escape(line :&str) -> String {
+    line.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            c => match win_ansi(c) {
+                Some(code) => format!("\\{:03o}", code),
+                None => String::from("?"),
+            },
+        })
+        .collect()
+}
+
+/// The WinAnsi code of a character beyond ASCII: Latin-1 from 0xA0 on, some punctuation below.
+fn win_ansi(c :char) -> Option<u8> {
+    match c {
+        '\u{a0}'..='\u{ff}' => Some(c as u8),
+        '€' => Some(0x80),
+        '…' => Some(0x85),
+        '‘' => Some(0x91),
+        '’' => Some(0x92),
+        '“' => Some(0x93),
+        '”' => Some(0x94),
+        '•' => Some(0x95),
+        '–' => Some(0x96),
+        '—' => Some(0x97),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_and_read(name :&str, lines :&[String]) -> String {
+        let path = std::env::temp_dir().join(name);
+        write_text(path.to_str().unwrap(), lines).unwrap();
+        let pdf = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(pdf.is_ascii());
+        String::from_utf8(pdf).unwrap()
+    }
+
+    /// Checks header, the byte offsets of the cross reference table and the trailer, returns the number of pages.
+    fn assert_structure(pdf :&str) -> usize {
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        let startxref = pdf.rfind("startxref\n").unwrap();
+        let xref_offset :usize = pdf[startxref + 10..].lines().next().unwrap().parse().unwrap();
+        assert!(pdf[xref_offset..].starts_with("xref\n0 "));
+        let offsets :Vec<usize> = pdf[xref_offset..].lines()
+            .filter(|line| line.ends_with(" 00000 n "))
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        for (index, offset) in offsets.iter().enumerate() {
+            assert!(pdf[*offset..].starts_with(&format!("{} 0 obj\n", index + 1)), "object {} not at {}", index + 1, offset);
+        }
+        assert!(pdf.contains(&format!("trailer\n<< /Size {} /Root 1 0 R >>", offsets.len() + 1)));
+        pdf.matches("/Type /Page ").count()
+    }
+
+    #[test]
+    fn report_of_several_pages() {
+        let lines :Vec<String> = (0..150).map(|index| format!("line {} (of 150)", index)).collect();
+        let pdf = write_and_read("fslibre_plot_pages.pdf", &lines);
+        assert_eq!(assert_structure(&pdf), (150 + PAGE_LINES - 1) / PAGE_LINES);
+        assert!(pdf.contains("(line 0 \\(of 150\\)) Tj"));
+    }
+
+    #[test]
+    fn empty_report_has_one_page() {
+        assert_eq!(assert_structure(&write_and_read("fslibre_plot_empty.pdf", &[])), 1);
+    }
+
+    #[test]
+    fn text_is_encoded_as_win_ansi() {
+        assert_eq!(escape("37 °C, 5 µg, Müller – 2 €"), "37 \\260C, 5 \\265g, M\\374ller \\226 2 \\200");
+        assert_eq!(escape("→"), "?");
+    }
+
+    #[test]
+    fn long_lines_are_wrapped() {
+        let pieces = wrap(&"x".repeat(LINE_CHARS + 10));
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[1], format!("    {}", "x".repeat(10)));
+    }
+}
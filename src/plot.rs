@@ -1,150 +1,524 @@
+use crate::analytics::{self, TimeInRange};
+use crate::artefacts;
+use crate::config::Config;
+use crate::episodes;
+use crate::insulin;
 use crate::model::FsLibreLine;
+use crate::on_board;
+use crate::sessions;
+use crate::trend;
 
-use std::ops::{Sub, Add};
+use std::ops::{Sub, Add, Range};
 use std::io::BufReader;
 use std::fs::File;
 use chrono::{Duration, NaiveTime, NaiveDateTime};
 use plotters::prelude::*;
-use image::{imageops::FilterType, ImageFormat};
+use plotters::coord::Shift;
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
 
-pub fn plot(data_of_day :&Vec<FsLibreLine>, path :&str, title :&str) -> Result<(), Box<dyn std::error::Error>> {
-    let y_min = -100;
-    let y_max = 350;
+/// Plots one day. `earlier` are the lines before the day, doses and meals of the last hours
+/// still count for the insulin and carbs on board.
+pub fn plot(data_of_day :&Vec<FsLibreLine>, earlier :&[FsLibreLine], path :&str, title :&str, config :&Config) -> Result<(), Box<dyn std::error::Error>> {
     let target_range_min = 60;
     let target_range_max = 180;
 
-    let backend = BitMapBackend::new(path, (800, 600));
+    let hist_dots = if config.hide_hist {Vec::new()} else {collect_hist_dots(data_of_day)};
+    let scan_dots = if config.hide_scans {Vec::new()} else {collect_scan_dots(data_of_day)};
+    let mut line_dots = [hist_dots.as_slice(), scan_dots.as_slice()].concat();
+    line_dots.sort_by_key(|dot| dot.0);
+    let (y_min, y_max) = y_range(&line_dots, config);
+
+    let backend = BitMapBackend::new(path, (800, 725));
     let root = backend.into_drawing_area();
     root.fill(&WHITE)?;
+    let root = root.titled(title, ("sans-serif", 30.0).into_font())?;
+    let (header, root) = root.split_vertically(25);
+    header.draw(&Text::new(format!("insulin {}  |  sensor data {:.0}%", insulin::describe(&insulin::insulin_dose(data_of_day)),
+        analytics::sensor_coverage(data_of_day) * 100.0),
+        (60, 0), ("sans-serif", 16).into_font()))?;
+    let (upper, lower) = root.split_vertically(400);
 
     let (from_date, to_date) = (
         to_duration(&data_of_day[0].timestamp),
         to_duration(&data_of_day[data_of_day.len() - 1].timestamp),
     );
 
-    let mut chart = ChartBuilder::on(&root)
-        .x_label_area_size(40)
+    // the curves over the event panels need an axis on the right, all charts keep the same width
+    let right_label_area = if config.show_iob || config.show_cob {50} else {0};
+    let mut chart = ChartBuilder::on(&upper)
         .y_label_area_size(50)
-        .caption(title, ("sans-serif", 30.0).into_font())
+        .margin_right(right_label_area)
         .build_cartesian_2d(from_date..to_date, y_min..y_max)?;
 
     chart.configure_mesh()
         .light_line_style(&WHITE)
-        .x_label_formatter(&|x| format!("{}:00", x.num_hours()))
         .y_label_formatter(&|y| format!("{}", y))
         .draw()?;
 
-    // red area
-    chart.plotting_area().draw(&Rectangle::new(
-        [(zero_duration(), 0), (max_width_duration(), target_range_min)],
-        RGBColor(255,179, 179).filled()))?;
-    // target area
-    chart.plotting_area().draw(&Rectangle::new(
-        [(zero_duration(), target_range_min), (max_width_duration(), target_range_max)],
-        RGBColor(173,216,230).filled()))?;
-
-    let mut line_dots = Vec::new();
-    chart.draw_series(
-        data_of_day.iter()
-            .map(|x| {
-                let y :i32 = (if x.gluco_scanned > 0 {x.gluco_scanned} else {x.gluco_hist}) as i32;
-                if y > 0 {
-                    let style = if y > target_range_min {BLACK.filled()} else {RED.filled()};
-                    let x = to_duration(&x.timestamp);
-                    line_dots.push((x, y));
-                    Circle::new((x, y), 5, style)
-                } else {
-                    Circle::new((Duration::seconds(0),0), 0, BLACK.filled())
-                }
-            }),
-    )?;
-    chart.draw_series(LineSeries::new(line_dots, &BLACK))?;
-
-    let legend_y = -10;
-    let legend_line_height = 2;
-    let legend_text_height = 20;
-    let legend_text_line_offset = 6;
-    let legend_y_insulin_fast = legend_y - legend_line_height - 3;
-    let legend_y_food = legend_y_insulin_fast - legend_text_height;
-    let legend_y_insulin_slow = legend_y_food - legend_text_height;
-    chart.plotting_area().draw(&Rectangle::new(
-        [(zero_duration(), legend_y), (max_width_duration(), legend_y - legend_line_height)],
-        BLACK.filled()))?;
-    chart.plotting_area().draw(&Rectangle::new(
-        [
-            (zero_duration(), legend_y_food + legend_text_line_offset),
-            (max_width_duration(), legend_y_food + legend_text_line_offset - legend_line_height / 2)
-        ],
-        BLACK.filled()))?;
-    chart.plotting_area().draw(&Rectangle::new(
-        [
-            (zero_duration(), legend_y_insulin_slow + legend_text_line_offset),
-            (max_width_duration(), legend_y_insulin_slow + legend_text_line_offset - legend_line_height / 2)
-        ],
-        BLACK.filled()))?;
+    // red area and target area, each cut to the visible y-range
+    let areas = [
+        (y_min.max(0), target_range_min.min(y_max), RGBColor(255,179, 179)),
+        (target_range_min.max(y_min), target_range_max.min(y_max), RGBColor(173,216,230)),
+    ];
+    for (bottom, top, color) in areas.iter() {
+        if bottom < top {
+            chart.plotting_area().draw(&Rectangle::new(
+                [(zero_duration(), *bottom), (max_width_duration(), *top)],
+                color.filled()))?;
+        }
+    }
 
-    let font_default :FontDesc = ("sans-serif", 14).into_font();
-    font_default.color(&BLACK);
-    /*
-    note: rendering icons as emoji-text has issues
-    let font_icons :FontDesc = ("monospace", 10).into_font();
-    font_icons.color(&BLACK);
-    let font_icons_slow :FontDesc = ("monospace", 10).into_font();
-    font_icons_slow.color(&GREEN);
-    chart.plotting_area().draw(&Text::new("💉", (zero_duration(), legend_y_insulin_fast), font_icons.clone()))?;
-    chart.plotting_area().draw(&Text::new("🍎", (zero_duration(), legend_y_food),font_icons.clone()))?;
-    chart.plotting_area().draw(&Text::new("💉", (zero_duration(), legend_y_insulin_slow), font_icons_slow.clone()))?;
-     */
-    let image_syringe = image::load(BufReader::new(File::open(build_image_path_syringe())?),ImageFormat::Png)?.resize_exact(12, 12, FilterType::Nearest);
-    let image_food = image::load(BufReader::new(File::open(build_image_path_food())?),ImageFormat::Png)?.resize_exact(12, 12, FilterType::Nearest);
-    let image_syringe_slow = image::load(BufReader::new(File::open(build_image_path_syringe_slow())?),ImageFormat::Png)?.resize_exact(12, 12, FilterType::Nearest);
-    let bitmap_syringe_legend : BitMapElement<_> = ((zero_duration(), legend_y_insulin_fast), image_syringe.clone()).into();
-    let bitmap_food_legend : BitMapElement<_> = ((zero_duration(), legend_y_food), image_food.clone()).into();
-    let bitmap_syringe_slow_legend : BitMapElement<_> = ((zero_duration(), legend_y_insulin_slow), image_syringe_slow.clone()).into();
-    chart.draw_series(std::iter::once(bitmap_syringe_legend))?;
-    chart.draw_series(std::iter::once(bitmap_food_legend))?;
-    chart.draw_series(std::iter::once(bitmap_syringe_slow_legend))?;
-
-    let info_icon_y_fast_insulin = 340;
-    let info_icon_y_food = info_icon_y_fast_insulin - 12;
-    let info_icon_y_slow_insulin = info_icon_y_food - 12;
+    let segments = split_at_gaps(&line_dots, Duration::minutes(config.gap_minutes));
+    if config.shade_gaps {
+        let font_gap :FontDesc = ("sans-serif", 14).into_font();
+        // below the target range if that is visible, in the middle of the chart otherwise
+        let text_y = if y_min < target_range_max / 2 && target_range_max / 2 < y_max {target_range_max / 2} else {y_min + (y_max - y_min) / 2};
+        for neighbours in segments.windows(2) {
+            let gap_start = neighbours[0][neighbours[0].len() - 1].0;
+            let gap_end = neighbours[1][0].0;
+            chart.plotting_area().draw(&Rectangle::new(
+                [(gap_start, y_min), (gap_end, y_max)],
+                RGBColor(220, 220, 220).filled()))?;
+            let gap_middle = gap_start + (gap_end - gap_start) / 2;
+            chart.plotting_area().draw(&Text::new("no data", (gap_middle, text_y), font_gap.clone()))?;
+        }
+    }
+
+    for session in sessions::sensor_sessions(data_of_day).iter().filter(|session| session.started_by_record) {
+        let warm_up_end = session.start + Duration::minutes(sessions::WARM_UP_MINUTES).max(session.warm_up());
+        // a warm-up running past midnight ends at the edge of the day
+        let warm_up_end = (warm_up_end - session.start.date().and_hms(0, 0, 0)).min(max_width_duration());
+        chart.plotting_area().draw(&Rectangle::new(
+            [(to_duration(&session.start), y_min), (warm_up_end, y_max)],
+            RGBColor(220, 220, 220).mix(0.6).filled()))?;
+        chart.plotting_area().draw(&Text::new("sensor warm-up", (to_duration(&session.start), y_max - 30), ("sans-serif", 12).into_font()))?;
+    }
+    for episode in episodes::hypo_episodes(data_of_day, config.hypo_limit, config) {
+        chart.plotting_area().draw(&Rectangle::new(
+            [(to_duration(&episode.start), y_min), (to_duration(&episode.end), y_max)],
+            RED.mix(0.15).filled()))?;
+    }
+    for episode in episodes::hyper_episodes(data_of_day, config.hyper_limit, config) {
+        chart.plotting_area().draw(&Rectangle::new(
+            [(to_duration(&episode.start), y_min), (to_duration(&episode.end), y_max)],
+            RGBColor(255, 140, 0).mix(0.15).filled()))?;
+    }
+
+    if !hist_dots.is_empty() {
+        chart.draw_series(
+            hist_dots.iter()
+                .map(|(x, y)| {
+                    let style = if *y > target_range_min {BLACK.filled()} else {RED.filled()};
+                    Circle::new((*x, *y), 3, style)
+                }),
+        )?
+            .label("history")
+            .legend(|(x, y)| Circle::new((x, y), 3, BLACK.filled()));
+    }
+    if !scan_dots.is_empty() {
+        chart.draw_series(
+            scan_dots.iter()
+                .map(|(x, y)| {
+                    let style = if *y > target_range_min {BLACK.filled()} else {RED.filled()};
+                    EmptyElement::at((*x, *y)) + Polygon::new(vec![(0, -6), (6, 0), (0, 6), (-6, 0)], style)
+                }),
+        )?
+            .label("scan")
+            .legend(|(x, y)| Polygon::new(vec![(x, y - 6), (x + 6, y), (x, y + 6), (x - 6, y)], BLACK.filled()));
+
+        // the trend arrow of the reader above every scan
+        let arrows = trend::trend_arrows(data_of_day);
+        chart.draw_series(data_of_day.iter()
+            .filter(|line| line.gluco_scanned > 0)
+            .filter_map(|line| arrows.iter().find(|arrow| arrow.0 == line.timestamp)
+                .map(|arrow| (to_duration(&line.timestamp), line.gluco_scanned as i32, arrow.1)))
+            .map(|(x, y, arrow)| {
+                let (shaft, head) = arrow_shape(arrow.angle());
+                EmptyElement::at((x, y)) + PathElement::new(shaft, RGBColor(90, 90, 90).stroke_width(2)) + Polygon::new(head, RGBColor(90, 90, 90).filled())
+            }))?;
+    }
+    for segment in segments {
+        chart.draw_series(LineSeries::new(segment, &BLACK))?;
+    }
+    let artefacts = artefacts::detect_artefacts(data_of_day, config);
+    let artefact_dots :Vec<&(Duration, i32)> = line_dots.iter()
+        .filter(|dot| artefacts.iter().any(|artefact| to_duration(&artefact.start) <= dot.0 && dot.0 <= to_duration(&artefact.end)))
+        .collect();
+    if !artefact_dots.is_empty() {
+        chart.draw_series(artefact_dots.iter().map(|dot| Cross::new(**dot, 6, RGBColor(255, 0, 255).stroke_width(2))))?
+            .label("artefact")
+            .legend(|(x, y)| Cross::new((x, y), 5, RGBColor(255, 0, 255).stroke_width(2)));
+    }
+
+    // bolus, basal and carbohydrates are drawn as bars in panels below the glucose chart,
+    // sharing its time axis but each panel on its own scale
+    let mut bolus_bars = Vec::new();
+    let mut basal_bars = Vec::new();
+    let mut food_bars = Vec::new();
     for x in data_of_day {
-        let fast_insulin :i32 = (if x.fast_insulin_units > 0 {x.fast_insulin_units} else if x.fast_insulin > 0 {x.fast_insulin} else {x.fast_insulin_non_numeric}) as i32;
-        let slow_insulin :i32 = (if x.slow_insulin_units > 0 {x.slow_insulin_units} else if x.slow_insulin > 0 {x.slow_insulin} else if x.slow_insulin_non_numeric > 0 {x.slow_insulin_non_numeric} else {x.carbohydrate}) as i32;
-        let food :i32 = (if x.food > 0 {x.food} else if x.food_non_numeric > 0 {x.food_non_numeric} else {0}) as i32;
+        let fast_insulin = x.fast_insulin_value() as i32;
+        let slow_insulin = x.slow_insulin_value() as i32;
+        let food = x.food_value() as i32;
+        let time = to_duration(&x.timestamp);
 
         if fast_insulin > 0 {
-            //chart.plotting_area().draw(&Text::new("💉", (to_duration(&x.timestamp), info_icon_y_fast_insulin), font_icons.clone()))?;
-            let bitmap_syringe_usage : BitMapElement<_> = ((to_duration(&x.timestamp), info_icon_y_fast_insulin), image_syringe.clone()).into();
-            chart.draw_series(std::iter::once(bitmap_syringe_usage))?;
-            chart.plotting_area().draw(&Text::new(fast_insulin.to_string(), (to_duration(&x.timestamp), legend_y_insulin_fast), font_default.clone()))?;
+            bolus_bars.push((time, fast_insulin));
         }
         if food > 0 {
-            //chart.plotting_area().draw(&Text::new("🍎", (to_duration(&x.timestamp), info_icon_y_food), font_icons.clone()))?;
-            let bitmap_food_usage : BitMapElement<_> = ((to_duration(&x.timestamp), info_icon_y_food), image_food.clone()).into();
-            chart.draw_series(std::iter::once(bitmap_food_usage))?;
-            chart.plotting_area().draw(&Text::new(food.to_string(), (to_duration(&x.timestamp), legend_y_food), font_default.clone()))?;
+            food_bars.push((time, food));
         }
         if slow_insulin > 0 {
-            //chart.plotting_area().draw(&Text::new("💉", (to_duration(&x.timestamp), info_icon_y_slow_insulin), font_icons_slow.clone()))?;
-            let bitmap_syringe_slow_usage : BitMapElement<_> = ((to_duration(&x.timestamp), info_icon_y_slow_insulin), image_syringe_slow.clone()).into();
-            chart.plotting_area().draw(&Text::new(slow_insulin.to_string(), (to_duration(&x.timestamp), legend_y_insulin_slow), font_default.clone()))?;
-            chart.draw_series(std::iter::once(bitmap_syringe_slow_usage))?;
+            basal_bars.push((time, slow_insulin));
         }
         if fast_insulin > 0 || slow_insulin > 0 || food > 0 {
-            chart.plotting_area().draw(&Rectangle::new(
-                [(to_duration_offset(&x.timestamp), legend_y_insulin_slow - legend_text_height), (to_duration_offset(&x.timestamp), 400)],
-                BLACK.filled()))?;
+            let line_x = to_duration_offset(&x.timestamp);
+            chart.draw_series(std::iter::once(PathElement::new(vec![(line_x, y_min), (line_x, y_max)], &BLACK)))?;
+        }
+    }
+
+    /*
+    note: rendering icons as emoji-text has issues, so the icons are loaded from images
+     */
+    let image_syringe = image::load(BufReader::new(File::open(build_image_path_syringe())?),ImageFormat::Png)?.resize_exact(12, 12, FilterType::Nearest);
+    let image_food = image::load(BufReader::new(File::open(build_image_path_food())?),ImageFormat::Png)?.resize_exact(12, 12, FilterType::Nearest);
+    let image_syringe_slow = image::load(BufReader::new(File::open(build_image_path_syringe_slow())?),ImageFormat::Png)?.resize_exact(12, 12, FilterType::Nearest);
+
+    let insulin_on_board = if config.show_iob {
+        let profile = config.insulin_profile();
+        let doses = with_preceding(earlier, data_of_day, Duration::minutes(profile.duration_minutes));
+        Some(OnBoardCurve {label: "insulin on board", color: RGBColor(0, 0, 139), points: sample_curve(data_of_day,
+            |time| on_board::insulin_on_board(&doses, &profile, time))})
+    } else {
+        None
+    };
+    let carbs_on_board = if config.show_cob {
+        let profile = config.carb_profile();
+        let meals = with_preceding(earlier, data_of_day, Duration::minutes(profile.duration_minutes));
+        Some(OnBoardCurve {label: "carbs on board", color: RGBColor(139, 69, 19), points: sample_curve(data_of_day,
+            |time| on_board::carbs_on_board(&meals, &profile, time))})
+    } else {
+        None
+    };
+
+    let (insulin_area, food_area) = lower.split_vertically(110);
+    draw_bar_panel(&insulin_area, &TimeAxis {range: from_date..to_date, labels: false, right_label_area}, &[
+        BarSeries {label: "bolus", color: RGBColor(30, 144, 255), bars: &bolus_bars, icon: &image_syringe},
+        BarSeries {label: "basal", color: RGBColor(138, 43, 226), bars: &basal_bars, icon: &image_syringe_slow},
+    ], insulin_on_board, 10, "U")?;
+    draw_bar_panel(&food_area, &TimeAxis {range: from_date..to_date, labels: true, right_label_area}, &[
+        BarSeries {label: "carbs", color: RGBColor(255, 140, 0), bars: &food_bars, icon: &image_food},
+    ], carbs_on_board, 50, "g")?;
+
+    if let Some(tir) = analytics::time_in_range(data_of_day, config) {
+        draw_time_in_range(&upper, &tir)?;
+    }
+
+    if !hist_dots.is_empty() || !scan_dots.is_empty() {
+        chart.configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .background_style(&WHITE.mix(0.8))
+            .border_style(&BLACK)
+            .draw()?;
+    }
+
+    Ok(())
+}
+
+struct BarSeries<'a> {
+    label :&'a str,
+    color :RGBColor,
+    bars :&'a [(Duration, i32)],
+    icon :&'a DynamicImage,
+}
+
+/// A curve over an event panel, e.g. insulin on board, drawn on the right axis of the panel.
+struct OnBoardCurve<'a> {
+    label :&'a str,
+    color :RGBColor,
+    points :Vec<(Duration, f64)>,
+}
+
+/// Time axis of an event panel, shared with the glucose chart.
+struct TimeAxis {
+    range :Range<Duration>,
+    labels :bool,
+    right_label_area :u32,
+}
+
+/// Draws one event panel: the bars of all series side by side at their event time,
+/// each labelled with its value, and a legend with the series icons in the upper left corner.
+fn draw_bar_panel(area :&DrawingArea<BitMapBackend, Shift>, axis :&TimeAxis, series :&[BarSeries], curve :Option<OnBoardCurve>, min_y_max :i32, unit :&str) -> Result<(), Box<dyn std::error::Error>> {
+    let bar_width = Duration::minutes(20);
+    let data_max = series.iter().flat_map(|bar_series| bar_series.bars.iter()).map(|bar| bar.1).max().unwrap_or(0);
+    // leave room for the value labels above the bars
+    let y_max = data_max.max(min_y_max) * 4 / 3;
+    let x_start = axis.range.start;
+    let curve_max = curve.iter().flat_map(|curve| curve.points.iter()).map(|point| point.1).fold(1.0, f64::max) * 4.0 / 3.0;
+
+    let mut panel = ChartBuilder::on(area)
+        .margin_bottom(if axis.labels {0} else {8})
+        .x_label_area_size(if axis.labels {40} else {0})
+        .y_label_area_size(50)
+        .right_y_label_area_size(axis.right_label_area)
+        .build_cartesian_2d(axis.range.clone(), 0..y_max)?
+        .set_secondary_coord(axis.range.clone(), 0.0..curve_max);
+
+    panel.configure_mesh()
+        .light_line_style(&WHITE)
+        .y_labels(3)
+        .y_label_formatter(&|y| format!("{}{}", y, unit))
+        .x_label_formatter(&|x| format!("{}:00", x.num_hours()))
+        .draw()?;
+
+    if let Some(curve) = &curve {
+        panel.configure_secondary_axes()
+            .y_labels(3)
+            .y_label_formatter(&|y| format!("{:.0}{}", y, unit))
+            .draw()?;
+        panel.draw_secondary_series(LineSeries::new(curve.points.clone(), curve.color.stroke_width(2)))?;
+    }
+
+    let font_default :FontDesc = ("sans-serif", 14).into_font();
+    let slot_width = bar_width / series.len() as i32;
+    let mut labels = Vec::new();
+    for (index, bar_series) in series.iter().enumerate() {
+        let slot_start = slot_width * index as i32 - bar_width / 2;
+        let style = bar_series.color.filled();
+        panel.draw_series(bar_series.bars.iter()
+            .map(|(time, value)| Rectangle::new([(*time + slot_start, 0), (*time + slot_start + slot_width, *value)], style.clone())))?;
+        for (time, value) in bar_series.bars {
+            labels.push((*time + slot_start, *value));
+        }
+        let legend_x = 4 + 80 * index as i32;
+        panel.draw_series(std::iter::once(
+            EmptyElement::at((x_start, y_max))
+                + Rectangle::new([(legend_x, 4), (legend_x + 10, 14)], style.clone())
+                + BitMapElement::from(((legend_x + 14, 3), bar_series.icon.clone()))
+                + Text::new(bar_series.label.to_string(), (legend_x + 30, 2), font_default.clone())))?;
+    }
+    let legend_width = 80 * series.len() as i32 + curve.as_ref().map(|curve| 30 + 7 * curve.label.len() as i32).unwrap_or(0);
+    if let Some(curve) = &curve {
+        let legend_x = 4 + 80 * series.len() as i32;
+        panel.draw_series(std::iter::once(
+            EmptyElement::at((x_start, y_max))
+                + PathElement::new(vec![(legend_x, 9), (legend_x + 20, 9)], curve.color.stroke_width(2))
+                + Text::new(curve.label.to_string(), (legend_x + 26, 2), font_default.clone())))?;
+    }
+
+    // labels of events close to each other are moved aside, the legend must stay readable as well
+    labels.sort_by_key(|label| label.0);
+    let legend_corner = panel.backend_coord(&(x_start, y_max));
+    let mut label_boxes = vec![(legend_corner.0, legend_corner.1, legend_corner.0 + legend_width, legend_corner.1 + 16)];
+    for (time, value) in &labels {
+        let text = value.to_string();
+        let (width, height) = area.estimate_text_size(&text, &font_default.clone().into())?;
+        let corner = panel.backend_coord(&(*time, *value));
+        label_boxes.push((corner.0, corner.1 - 16, corner.0 + width as i32, corner.1 - 16 + height as i32));
+    }
+    let (left, top) = panel.backend_coord(&(x_start, y_max));
+    let bounds = Bounds {left, top, right: panel.backend_coord(&(axis.range.end, 0)).0};
+    let mut offsets = layout_labels(&label_boxes[..1], &label_boxes[1..], &bounds);
+    if offsets.iter().any(|offset| offset.is_none()) {
+        // labels without room left are counted in the upper right corner, which is kept free for that
+        let (width, height) = area.estimate_text_size("+99", &font_default.clone().into())?;
+        label_boxes.insert(1, (bounds.right - width as i32, bounds.top, bounds.right, bounds.top + height as i32));
+        offsets = layout_labels(&label_boxes[..2], &label_boxes[2..], &bounds);
+        let hidden = offsets.iter().filter(|offset| offset.is_none()).count();
+        panel.draw_series(std::iter::once(
+            EmptyElement::at((axis.range.end, y_max)) + Text::new(format!("+{}", hidden), (-(width as i32), 0), font_default.clone())))?;
+    }
+    let slot_pixels = panel.backend_coord(&(x_start + slot_width, 0)).0 - panel.backend_coord(&(x_start, 0)).0;
+    for ((time, value), offset) in labels.iter().zip(offsets) {
+        let offset = match offset {
+            Some(offset) => offset,
+            None => continue,
+        };
+        let label = EmptyElement::at((*time, *value)) + Text::new(value.to_string(), (offset.0, offset.1 - 16), font_default.clone());
+        if offset != (0, 0) {
+            // leader line from the label back to the top of its bar
+            panel.draw_series(std::iter::once(
+                label + PathElement::new(vec![(slot_pixels / 2, 0), (offset.0, offset.1 - 8)], &BLACK)))?;
+        } else {
+            panel.draw_series(std::iter::once(label))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A box `(x0, y0, x1, y1)` in pixels.
+type LabelBox = (i32, i32, i32, i32);
+
+/// Edges of the plotting area of a panel in pixels.
+struct Bounds {
+    left :i32,
+    top :i32,
+    right :i32,
+}
+
+/// Places the label boxes one after another, ordered by time: a label overlapping a fixed box
+/// or an already placed label is moved right behind it until it is free. Labels stay between the
+/// left and right edge; where there is no room left to the right, the label goes one row up.
+/// Returns the horizontal and vertical offset of every label, `None` for labels above the top edge.
+fn layout_labels(fixed :&[LabelBox], labels :&[LabelBox], bounds :&Bounds) -> Vec<Option<(i32, i32)>> {
+    let padding = 3;
+    let mut placed = fixed.to_vec();
+    let mut offsets = Vec::new();
+    for label in labels {
+        let leftmost = (bounds.right - label.2).min(0).max(bounds.left - label.0);
+        let mut offset = (leftmost, 0);
+        let free = loop {
+            if label.1 + offset.1 < bounds.top {
+                break None;
+            }
+            let obstacle = placed.iter()
+                .find(|other| label.0 + offset.0 < other.2 + padding && other.0 < label.2 + offset.0 + padding
+                    && label.1 + offset.1 < other.3 && other.1 < label.3 + offset.1);
+            match obstacle {
+                Some(obstacle) => {
+                    offset.0 = obstacle.2 + padding - label.0;
+                    if label.2 + offset.0 > bounds.right {
+                        offset = (leftmost, offset.1 - (label.3 - label.1) - padding);
+                    }
+                }
+                None => break Some(offset),
+            }
+        };
+        if let Some(offset) = free {
+            placed.push((label.0 + offset.0, label.1 + offset.1, label.2 + offset.0, label.3 + offset.1));
+        }
+        offsets.push(free);
+    }
+    offsets
+}
+
+/// Draws the time in range as a stacked bar in the upper right corner of the glucose chart,
+/// very low at the bottom and very high at the top.
+fn draw_time_in_range(area :&DrawingArea<BitMapBackend, Shift>, tir :&TimeInRange) -> Result<(), Box<dyn std::error::Error>> {
+    let (width, _) = area.dim_in_pixel();
+    let bar_height = 100.0;
+    let (x0, y0) = (width as i32 - 96, 8);
+    let (bar_x0, bar_x1) = (x0 + 66, x0 + 84);
+    let bar_bottom = y0 + 6 + bar_height as i32;
+    area.draw(&Rectangle::new([(x0, y0), (x0 + 90, bar_bottom + 6)], WHITE.mix(0.8).filled()))?;
+    area.draw(&Rectangle::new([(x0, y0), (x0 + 90, bar_bottom + 6)], &BLACK))?;
+
+    let bands = [
+        (tir.very_low, RGBColor(139, 0, 0)),
+        (tir.low, RGBColor(255, 0, 0)),
+        (tir.in_range, RGBColor(50, 205, 50)),
+        (tir.high, RGBColor(255, 215, 0)),
+        (tir.very_high, RGBColor(255, 140, 0)),
+    ];
+    let mut share_below = 0.0;
+    for (share, color) in bands.iter() {
+        let bottom = bar_bottom - (share_below * bar_height) as i32;
+        share_below += share;
+        let top = bar_bottom - (share_below * bar_height) as i32;
+        if top < bottom {
+            area.draw(&Rectangle::new([(bar_x0, top), (bar_x1, bottom)], color.filled()))?;
         }
     }
 
+    let font :FontDesc = ("sans-serif", 13).into_font();
+    area.draw(&Text::new("TIR", (x0 + 6, y0 + 8), font.clone()))?;
+    area.draw(&Text::new(format!("{:.0}%", tir.in_range * 100.0), (x0 + 6, y0 + 26), font.clone()))?;
+    area.draw(&Text::new(format!("<70 {:.0}%", (tir.very_low + tir.low) * 100.0), (x0 + 6, y0 + 66), font.clone()))?;
+    area.draw(&Text::new(format!(">180 {:.0}%", (tir.high + tir.very_high) * 100.0), (x0 + 6, y0 + 84), font))?;
+
     Ok(())
 }
 
-fn to_duration(timestamp :&NaiveDateTime) -> Duration {
+/// Uses the configured y-range if given. Otherwise the range starts at zero and
+/// is extended in steps of 50 so the highest reading plus the series legend fit in.
+fn y_range(line_dots :&[(Duration, i32)], config :&Config) -> (i32, i32) {
+    let min_span = 350;
+    let legend_headroom = 50;
+    let y_min = config.y_min.unwrap_or(0);
+    let data_max = line_dots.iter().map(|dot| dot.1).max().unwrap_or(0);
+    let auto_max = ((data_max + legend_headroom + 49) / 50 * 50).max(y_min + min_span);
+    (y_min, config.y_max.unwrap_or(auto_max))
+}
+
+fn collect_hist_dots(data_of_day :&[FsLibreLine]) -> Vec<(Duration, i32)> {
+    data_of_day.iter()
+        .filter(|x| x.gluco_hist > 0)
+        .map(|x| (to_duration(&x.timestamp), x.gluco_hist as i32))
+        .collect()
+}
+
+fn collect_scan_dots(data_of_day :&[FsLibreLine]) -> Vec<(Duration, i32)> {
+    data_of_day.iter()
+        .filter(|x| x.gluco_scanned > 0)
+        .map(|x| (to_duration(&x.timestamp), x.gluco_scanned as i32))
+        .collect()
+}
+
+/// Splits the glucose line wherever two neighbouring dots are further apart
+/// than `max_gap`, e.g. while a sensor was expired or not scanned in time.
+pub(crate) fn split_at_gaps(line_dots :&[(Duration, i32)], max_gap :Duration) -> Vec<Vec<(Duration, i32)>> {
+    let mut segments :Vec<Vec<(Duration, i32)>> = Vec::new();
+    let mut segment :Vec<(Duration, i32)> = Vec::new();
+    for dot in line_dots {
+        if let Some(prev_dot) = segment.last() {
+            if dot.0 - prev_dot.0 > max_gap {
+                segments.push(segment);
+                segment = Vec::new();
+            }
+        }
+        segment.push(*dot);
+    }
+    if !segment.is_empty() {
+        segments.push(segment);
+    }
+    segments
+}
+
+pub(crate) fn to_duration(timestamp :&NaiveDateTime) -> Duration {
     timestamp.time().signed_duration_since(NaiveTime::from_hms(0, 0, 0))
 }
 
+/// Pixel offsets from a point of the chart.
+type Pixels = Vec<(i32, i32)>;
+
+/// Shaft and head of a trend arrow drawn above a scan, pointing in `angle` degrees.
+fn arrow_shape(angle :f64) -> (Pixels, Pixels) {
+    let (center_x, center_y, length, head_length) = (0.0, -18.0, 14.0, 6.0);
+    // pixel rows grow downwards
+    let point = |angle :f64, distance :f64, from :(f64, f64)| {
+        let radians = angle.to_radians();
+        (from.0 + distance * radians.cos(), from.1 - distance * radians.sin())
+    };
+    let tail = point(angle, -length / 2.0, (center_x, center_y));
+    let tip = point(angle, length / 2.0, (center_x, center_y));
+    let left = point(angle + 150.0, head_length, tip);
+    let right = point(angle - 150.0, head_length, tip);
+    let pixel = |point :(f64, f64)| (point.0.round() as i32, point.1.round() as i32);
+    (vec![pixel(tail), pixel(tip)], vec![pixel(tip), pixel(left), pixel(right)])
+}
+
+/// Samples `value` every 5 minutes from the first to the last line of the day.
+fn sample_curve(data_of_day :&[FsLibreLine], value :impl Fn(NaiveDateTime) -> f64) -> Vec<(Duration, f64)> {
+    let (first, last) = (data_of_day[0].timestamp, data_of_day[data_of_day.len() - 1].timestamp);
+    let mut points = Vec::new();
+    let mut time = first;
+    while time <= last {
+        points.push((to_duration(&time), value(time)));
+        time += Duration::minutes(5);
+    }
+    points
+}
+
+/// The lines of the day preceded by the earlier lines from `span` before its first line.
+fn with_preceding(earlier :&[FsLibreLine], data_of_day :&[FsLibreLine], span :Duration) -> Vec<FsLibreLine> {
+    let start = data_of_day[0].timestamp - span;
+    earlier.iter()
+        .filter(|line| line.timestamp >= start)
+        .chain(data_of_day.iter())
+        .cloned()
+        .collect()
+}
+
 fn to_duration_offset(timestamp :&NaiveDateTime) -> Duration {
     let mut time = timestamp.time();
     time = time.sub(Duration::minutes(5));
@@ -181,3 +555,70 @@ fn build_image_path_food() -> String {
     let path = String::from(resources_dir()).add(delim()).add("apple.png");
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS :Bounds = Bounds {left: 50, top: 0, right: 750};
+
+    /// The boxes of the labels that were placed, moved by their offsets.
+    fn placed(labels :&[LabelBox], offsets :&[Option<(i32, i32)>]) -> Vec<LabelBox> {
+        labels.iter().zip(offsets)
+            .filter_map(|(label, offset)| offset.map(|offset| (label.0 + offset.0, label.1 + offset.1, label.2 + offset.0, label.3 + offset.1)))
+            .collect()
+    }
+
+    fn assert_free_and_inside(boxes :&[LabelBox]) {
+        for (index, a) in boxes.iter().enumerate() {
+            assert!(a.0 >= BOUNDS.left && a.2 <= BOUNDS.right && a.1 >= BOUNDS.top, "{:?} outside", a);
+            for b in &boxes[index + 1..] {
+                assert!(a.2 <= b.0 || b.2 <= a.0 || a.3 <= b.1 || b.3 <= a.1, "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    /// Labels 12 pixels wide and 14 high, their top at `y`.
+    fn labels_at(xs :&[i32], y :i32) -> Vec<LabelBox> {
+        xs.iter().map(|x| (*x, y, *x + 12, y + 14)).collect()
+    }
+
+    #[test]
+    fn labels_apart_stay_in_place() {
+        let labels = labels_at(&[100, 200, 300], 60);
+        assert_eq!(layout_labels(&[], &labels, &BOUNDS), vec![Some((0, 0)); 3]);
+    }
+
+    #[test]
+    fn dense_cluster_at_the_right_edge_stacks_up_to_the_top() {
+        // ten boluses in the last half hour of the day
+        let labels = labels_at(&[736, 738, 740, 742, 744, 746, 748, 750, 752, 754], 60);
+        let offsets = layout_labels(&[], &labels, &BOUNDS);
+        let boxes = placed(&labels, &offsets);
+        assert_free_and_inside(&boxes);
+        // rows 17 pixels apart fit 60 / 17 times above the first one
+        assert_eq!(boxes.len(), 4);
+        assert_eq!(offsets.iter().filter(|offset| offset.is_none()).count(), 6);
+    }
+
+    #[test]
+    fn dense_cluster_at_the_left_edge_starts_at_the_edge() {
+        let legend = (50, 0, 290, 16);
+        let labels = labels_at(&[40, 42, 44, 46, 48, 50, 52], 60);
+        let offsets = layout_labels(&[legend], &labels, &BOUNDS);
+        let mut boxes = placed(&labels, &offsets);
+        assert!(offsets.iter().all(|offset| offset.is_some()));
+        assert_eq!(boxes[0].0, BOUNDS.left);
+        boxes.push(legend);
+        assert_free_and_inside(&boxes);
+    }
+
+    #[test]
+    fn no_label_overlaps_another() {
+        let mut labels = labels_at(&[60, 65, 70, 300, 305, 700, 705, 710, 715, 720, 725, 730, 735, 740], 40);
+        labels.extend(labels_at(&[62, 302, 702, 738], 30));
+        labels.sort_by_key(|label| label.0);
+        let offsets = layout_labels(&[], &labels, &BOUNDS);
+        assert_free_and_inside(&placed(&labels, &offsets));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::analytics;
+use crate::config::Config;
+use crate::model::FsLibreLine;
+use crate::resample;
+use crate::variability;
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+use plotters::prelude::*;
+
+/// Width of the time of day bins.
+const BIN_MINUTES :i64 = 15;
+
+/// Percentiles of one time of day bin.
+struct Profile {
+    time :Duration,
+    p5 :f64,
+    p25 :f64,
+    median :f64,
+    p75 :f64,
+    p95 :f64,
+}
+
+/// Plots the Ambulatory Glucose Profile: the readings of all days on the configured grid laid over one day,
+/// as median with 25-75% and 5-95% bands, below a header with the key statistics of the period.
+pub fn plot_agp(data :&[FsLibreLine], path :&str, config :&Config) -> Result<(), Box<dyn std::error::Error>> {
+    let profiles = build_profiles(&resample::grid_values(&resample::regular_series(data, config)));
+    if profiles.is_empty() {
+        return Err(Box::from("no historic readings for an AGP"));
+    }
+    let y_max = profiles.iter().map(|profile| profile.p95).fold(350.0, f64::max).ceil();
+
+    let root = BitMapBackend::new(path, (1000, 600)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let title = format!("Ambulatory Glucose Profile {} - {}",
+        data[0].timestamp.format("%Y-%m-%d"), data[data.len() - 1].timestamp.format("%Y-%m-%d"));
+    let root = root.titled(&title, ("sans-serif", 30.0).into_font())?;
+    let (header, lower) = root.split_vertically(40);
+    header.draw(&Text::new(build_header(data, config), (60, 10), ("sans-serif", 18).into_font()))?;
+
+    let mut chart = ChartBuilder::on(&lower)
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(Duration::zero()..Duration::hours(24), 0.0..y_max)?;
+
+    chart.configure_mesh()
+        .light_line_style(&WHITE)
+        .x_labels(13)
+        .x_label_formatter(&|x| format!("{}:00", x.num_hours()))
+        .y_label_formatter(&|y| format!("{:.0}", y))
+        .y_desc("mg/dL")
+        .draw()?;
+
+    let outer_band = RGBColor(176, 196, 222);
+    let inner_band = RGBColor(70, 130, 180);
+    chart.draw_series(std::iter::once(Polygon::new(band(&profiles, |profile| profile.p5, |profile| profile.p95), outer_band.filled())))?
+        .label("5-95%")
+        .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 20, y + 5)], RGBColor(176, 196, 222).filled()));
+    chart.draw_series(std::iter::once(Polygon::new(band(&profiles, |profile| profile.p25, |profile| profile.p75), inner_band.filled())))?
+        .label("25-75%")
+        .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 20, y + 5)], RGBColor(70, 130, 180).filled()));
+    chart.draw_series(LineSeries::new(profiles.iter().map(|profile| (profile.time, profile.median)), BLACK.stroke_width(3)))?
+        .label("median")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLACK.stroke_width(3)));
+
+    for limit in [analytics::LOW_LIMIT, analytics::HIGH_LIMIT].iter() {
+        chart.draw_series(std::iter::once(PathElement::new(
+            vec![(Duration::zero(), *limit as f64), (Duration::hours(24), *limit as f64)], GREEN.stroke_width(2))))?;
+    }
+
+    chart.configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .background_style(&WHITE.mix(0.8))
+        .border_style(&BLACK)
+        .draw()?;
+
+    Ok(())
+}
+
+/// Sorts the grid values into time of day bins and computes their percentiles.
+/// Every bin also uses the values of its two neighbours, which smooths the curves.
+fn build_profiles(series :&[(NaiveDateTime, f64)]) -> Vec<Profile> {
+    let bin_count = (24 * 60 / BIN_MINUTES) as usize;
+    let mut bins :Vec<Vec<f64>> = vec![Vec::new(); bin_count];
+    for (time, glucose) in series {
+        let minute = time.time().num_seconds_from_midnight() as i64 / 60;
+        bins[(minute / BIN_MINUTES) as usize].push(*glucose);
+    }
+    let mut profiles = Vec::new();
+    for index in 0..bin_count {
+        let mut values :Vec<f64> = [(index + bin_count - 1) % bin_count, index, (index + 1) % bin_count].iter()
+            .flat_map(|neighbour| bins[*neighbour].iter().cloned())
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        profiles.push(Profile {
+            time: Duration::minutes(index as i64 * BIN_MINUTES + BIN_MINUTES / 2),
+            p5: analytics::percentile(&values, 0.05),
+            p25: analytics::percentile(&values, 0.25),
+            median: analytics::percentile(&values, 0.5),
+            p75: analytics::percentile(&values, 0.75),
+            p95: analytics::percentile(&values, 0.95),
+        });
+    }
+    profiles
+}
+
+/// Outline of the band between two percentiles: along the upper one, back along the lower one.
+fn band(profiles :&[Profile], lower :impl Fn(&Profile) -> f64, upper :impl Fn(&Profile) -> f64) -> Vec<(Duration, f64)> {
+    let mut points :Vec<(Duration, f64)> = profiles.iter().map(|profile| (profile.time, upper(profile))).collect();
+    points.extend(profiles.iter().rev().map(|profile| (profile.time, lower(profile))));
+    points
+}
+
+fn build_header(data :&[FsLibreLine], config :&Config) -> String {
+    let mut parts = Vec::new();
+    if let Some(tir) = analytics::time_in_range(data, config) {
+        parts.push(format!("TIR {:.0}%", tir.in_range * 100.0));
+        parts.push(format!("below {:.0}%", (tir.very_low + tir.low) * 100.0));
+        parts.push(format!("above {:.0}%", (tir.high + tir.very_high) * 100.0));
+    }
+    if let Some(gmi) = analytics::glucose_management_indicator(data, config) {
+        parts.push(format!("GMI {:.1}%", gmi.gmi_percent));
+    }
+    if let Some(variability) = variability::variability(data, config) {
+        parts.push(format!("CV {:.1}%", variability.cv));
+    }
+    parts.push(format!("sensor active {:.0}%", analytics::sensor_coverage(data) * 100.0));
+    parts.push(format!("{} days", analytics::period_days(data)));
+    parts.join("  |  ")
+}
+
@@ -0,0 +1,102 @@
+use crate::analytics;
+use crate::config::{CalendarSpan, Config};
+use crate::model::FsLibreLine;
+use crate::plot;
+use crate::variability;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use plotters::prelude::*;
+
+const CELL_WIDTH :u32 = 180;
+const CELL_HEIGHT :u32 = 130;
+const WEEKDAYS :[&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// First and last date of every week or month holding at least one of the days.
+pub fn calendar_pages(days :&[Vec<FsLibreLine>], span :CalendarSpan) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut pages :Vec<(NaiveDate, NaiveDate)> = Vec::new();
+    for data_of_day in days {
+        let date = data_of_day[0].timestamp.date();
+        if pages.last().is_some_and(|page| date <= page.1) {
+            continue;
+        }
+        pages.push(match span {
+            CalendarSpan::Week => {
+                let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                (monday, monday + Duration::days(6))
+            }
+            CalendarSpan::Month => {
+                let first = NaiveDate::from_ymd(date.year(), date.month(), 1);
+                let next_month = if date.month() == 12 {
+                    NaiveDate::from_ymd(date.year() + 1, 1, 1)
+                } else {
+                    NaiveDate::from_ymd(date.year(), date.month() + 1, 1)
+                };
+                (first, next_month.pred())
+            }
+        });
+    }
+    pages
+}
+
+/// Draws the days from `first` to `last` as a calendar: one row per week, one cell per day
+/// with a small glucose curve, the time in range and the mean.
+pub fn plot_calendar(days :&[Vec<FsLibreLine>], first :NaiveDate, last :NaiveDate, path :&str, config :&Config) -> Result<(), Box<dyn std::error::Error>> {
+    let grid_start = first - Duration::days(first.weekday().num_days_from_monday() as i64);
+    let rows = ((last - grid_start).num_days() / 7 + 1) as usize;
+
+    let root = BitMapBackend::new(path, (7 * CELL_WIDTH, rows as u32 * CELL_HEIGHT + 70)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let title = format!("{} - {}", first.format("%Y-%m-%d"), last.format("%Y-%m-%d"));
+    let root = root.titled(&title, ("sans-serif", 30.0).into_font())?;
+    let (header, grid) = root.split_vertically(25);
+    for (column, cell) in header.split_evenly((1, 7)).iter().enumerate() {
+        cell.draw(&Text::new(WEEKDAYS[column], (5, 5), ("sans-serif", 16).into_font()))?;
+    }
+
+    let cells = grid.split_evenly((rows, 7));
+    for (index, cell) in cells.iter().enumerate() {
+        let date = grid_start + Duration::days(index as i64);
+        if date < first || date > last {
+            continue;
+        }
+        let (width, height) = cell.dim_in_pixel();
+        cell.draw(&Rectangle::new([(0, 0), (width as i32 - 1, height as i32 - 1)], &BLACK.mix(0.3)))?;
+        match days.iter().find(|data_of_day| data_of_day[0].timestamp.date() == date) {
+            Some(data_of_day) => draw_cell(cell, date, data_of_day, config)?,
+            None => {
+                cell.draw(&Text::new(date.format("%d.%m.").to_string(), (5, 5), ("sans-serif", 14).into_font().color(&BLACK.mix(0.4))))?;
+                cell.draw(&Text::new("no data", (5, 25), ("sans-serif", 12).into_font().color(&BLACK.mix(0.4))))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn draw_cell(cell :&DrawingArea<BitMapBackend, plotters::coord::Shift>, date :NaiveDate, data_of_day :&[FsLibreLine], config :&Config) -> Result<(), Box<dyn std::error::Error>> {
+    cell.draw(&Text::new(date.format("%d.%m.").to_string(), (5, 5), ("sans-serif", 14).into_font()))?;
+    let series = analytics::glucose_series(data_of_day);
+    let mut summary = Vec::new();
+    if let Some(tir) = analytics::time_in_range(data_of_day, config) {
+        summary.push(format!("TIR {:.0}%", tir.in_range * 100.0));
+    }
+    if let Some(variability) = variability::variability(data_of_day, config) {
+        summary.push(format!("mean {:.0}", variability.mean));
+    }
+    cell.draw(&Text::new(summary.join("  "), (5, 22), ("sans-serif", 12).into_font()))?;
+
+    let dots :Vec<(Duration, i32)> = series.iter()
+        .map(|reading| (plot::to_duration(&reading.0), reading.1 as i32))
+        .collect();
+    let y_max = dots.iter().map(|dot| dot.1).max().unwrap_or(0).max(300);
+    let mut chart = ChartBuilder::on(cell)
+        .margin(4)
+        .margin_top(40)
+        .build_cartesian_2d(Duration::zero()..Duration::hours(24), 40..y_max)?;
+    chart.draw_series(std::iter::once(Rectangle::new(
+        [(Duration::zero(), analytics::LOW_LIMIT as i32), (Duration::hours(24), analytics::HIGH_LIMIT as i32)],
+        BLUE.mix(0.1).filled())))?;
+    for segment in plot::split_at_gaps(&dots, Duration::minutes(config.gap_minutes)) {
+        chart.draw_series(LineSeries::new(segment, &BLACK))?;
+    }
+    Ok(())
+}
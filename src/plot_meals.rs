@@ -0,0 +1,57 @@
+use crate::config::Config;
+use crate::meals::{self, MealResponse, MealSlot};
+use crate::model::FsLibreLine;
+use crate::variability;
+
+use chrono::Duration;
+use plotters::prelude::*;
+
+/// Plots the glucose after every meal relative to the glucose before it,
+/// one panel for breakfast, lunch and dinner.
+pub fn plot_meal_responses(data :&[FsLibreLine], path :&str, config :&Config) -> Result<(), Box<dyn std::error::Error>> {
+    let responses = meals::meal_responses(data, config);
+    if responses.is_empty() {
+        return Err(Box::from("no meals with readings before and after them"));
+    }
+    let rises = || responses.iter().flat_map(|response| response.trace.iter().map(move |reading| reading.1 - response.baseline));
+    let y_min = (rises().fold(0.0, f64::min) / 50.0).floor() * 50.0;
+    let y_max = (rises().fold(100.0, f64::max) / 50.0).ceil() * 50.0;
+
+    let root = BitMapBackend::new(path, (1200, 500)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let title = format!("glucose after meals {} - {}",
+        data[0].timestamp.format("%Y-%m-%d"), data[data.len() - 1].timestamp.format("%Y-%m-%d"));
+    let root = root.titled(&title, ("sans-serif", 30.0).into_font())?;
+    let colors = [RGBColor(255, 140, 0), RGBColor(34, 139, 34), RGBColor(70, 130, 180)];
+
+    for ((area, slot), color) in root.split_evenly((1, 3)).iter().zip(MealSlot::ALL.iter()).zip(colors.iter()) {
+        let slot_responses :Vec<&MealResponse> = responses.iter().filter(|response| response.slot == *slot).collect();
+        let caption = if slot_responses.is_empty() {
+            format!("{}: no meals", slot.name())
+        } else {
+            let deltas :Vec<f64> = slot_responses.iter().map(|response| response.delta).collect();
+            format!("{}: {} meals, mean rise {:.0}", slot.name(), slot_responses.len(), variability::mean(&deltas))
+        };
+        let mut chart = ChartBuilder::on(area)
+            .caption(caption, ("sans-serif", 16.0).into_font())
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(60)
+            .build_cartesian_2d(Duration::zero()..Duration::hours(meals::RESPONSE_HOURS), y_min..y_max)?;
+        chart.configure_mesh()
+            .light_line_style(&WHITE)
+            .x_labels(7)
+            .x_label_formatter(&|x| format!("+{}min", x.num_minutes()))
+            .y_label_formatter(&|y| format!("{:+.0}", y))
+            .y_desc("mg/dL")
+            .draw()?;
+        chart.draw_series(std::iter::once(PathElement::new(
+            vec![(Duration::zero(), 0.0), (Duration::hours(meals::RESPONSE_HOURS), 0.0)], BLACK.stroke_width(1))))?;
+        for response in slot_responses {
+            chart.draw_series(LineSeries::new(
+                response.trace.iter().map(|reading| (reading.0, reading.1 - response.baseline)),
+                &color.mix(0.5)))?;
+        }
+    }
+    Ok(())
+}
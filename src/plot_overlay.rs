@@ -0,0 +1,100 @@
+use crate::analytics;
+use crate::config::Config;
+use crate::model::FsLibreLine;
+use crate::plot;
+
+use chrono::{Duration, NaiveDate};
+use plotters::prelude::*;
+
+/// Plots the glucose curves of all days over one 24 hour axis, each day in its own colour,
+/// so patterns recurring at the same time of day stand out. The day `highlight` is drawn
+/// thick and black on top of the others. A key on the right maps the colours to the days.
+pub fn plot_overlay(days :&[Vec<FsLibreLine>], path :&str, highlight :Option<NaiveDate>, config :&Config) -> Result<(), Box<dyn std::error::Error>> {
+    if days.is_empty() {
+        return Err(Box::from("no days for an overlay"));
+    }
+    let curves :Vec<(NaiveDate, Vec<(Duration, i32)>)> = days.iter()
+        .map(|data_of_day| (data_of_day[0].timestamp.date(), analytics::glucose_series(data_of_day).iter()
+            .map(|reading| (plot::to_duration(&reading.0), reading.1 as i32))
+            .collect()))
+        .collect();
+    let data_max = curves.iter().flat_map(|curve| curve.1.iter().map(|dot| dot.1)).max().unwrap_or(0);
+    let y_min = config.y_min.unwrap_or(0);
+    let y_max = config.y_max.unwrap_or(((data_max + 49) / 50 * 50).max(y_min + 350));
+    let max_gap = Duration::minutes(config.gap_minutes);
+
+    let root = BitMapBackend::new(path, (1100, 600)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let title = format!("Daily overlay {} - {}", curves[0].0.format("%Y-%m-%d"), curves[curves.len() - 1].0.format("%Y-%m-%d"));
+    let (chart_area, key_area) = root.split_horizontally(1000);
+    draw_key(&key_area, &curves.iter().map(|curve| curve.0).collect::<Vec<NaiveDate>>(), highlight)?;
+
+    let mut chart = ChartBuilder::on(&chart_area)
+        .caption(title, ("sans-serif", 30.0).into_font())
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(Duration::zero()..Duration::hours(24), y_min..y_max)?;
+
+    chart.configure_mesh()
+        .light_line_style(&WHITE)
+        .x_labels(13)
+        .x_label_formatter(&|x| format!("{}:00", x.num_hours()))
+        .y_desc("mg/dL")
+        .draw()?;
+
+    chart.draw_series(std::iter::once(Rectangle::new(
+        [(Duration::zero(), analytics::LOW_LIMIT as i32), (Duration::hours(24), analytics::HIGH_LIMIT as i32)],
+        BLUE.mix(0.1).filled())))?;
+
+    for (index, (date, dots)) in curves.iter().enumerate() {
+        if Some(*date) == highlight {
+            continue;
+        }
+        let color = day_color(index, curves.len());
+        for segment in plot::split_at_gaps(dots, max_gap) {
+            chart.draw_series(LineSeries::new(segment, color.mix(0.6).stroke_width(1)))?;
+        }
+    }
+    if let Some((date, dots)) = curves.iter().find(|curve| Some(curve.0) == highlight) {
+        for (index, segment) in plot::split_at_gaps(dots, max_gap).into_iter().enumerate() {
+            let series = chart.draw_series(LineSeries::new(segment, BLACK.stroke_width(3)))?;
+            if index == 0 {
+                series.label(date.format("%Y-%m-%d").to_string())
+                    .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLACK.stroke_width(3)));
+            }
+        }
+        chart.configure_series_labels()
+            .position(SeriesLabelPosition::UpperRight)
+            .background_style(&WHITE.mix(0.8))
+            .border_style(&BLACK)
+            .draw()?;
+    }
+
+    Ok(())
+}
+
+/// Spreads the days over the colour wheel, leaving out the magenta end next to red.
+fn day_color(index :usize, days :usize) -> HSLColor {
+    HSLColor(0.8 * index as f64 / days as f64, 0.8, 0.45)
+}
+
+/// Draws a colour swatch for every day, with the dates as far as they fit one below the other.
+fn draw_key<DB :DrawingBackend>(area :&DrawingArea<DB, plotters::coord::Shift>, dates :&[NaiveDate], highlight :Option<NaiveDate>)
+    -> Result<(), Box<dyn std::error::Error>> where DB::ErrorType: 'static {
+    let top = 60;
+    let row_height = ((area.dim_in_pixel().1 as i32 - top - 40) / dates.len() as i32).clamp(1, 16);
+    // label every n-th day so the dates do not overlap
+    let label_every = (12 + row_height - 1) / row_height;
+    let font = ("sans-serif", 12).into_font();
+    for (index, date) in dates.iter().enumerate() {
+        let y = top + index as i32 * row_height;
+        let highlighted = Some(*date) == highlight;
+        let style = if highlighted {BLACK.filled()} else {day_color(index, dates.len()).filled()};
+        area.draw(&Rectangle::new([(5, y), (25, y + row_height - 1)], style))?;
+        if highlighted || index as i32 % label_every == 0 {
+            area.draw(&Text::new(date.format("%m-%d").to_string(), (30, y + row_height / 2 - 6), font.clone()))?;
+        }
+    }
+    Ok(())
+}
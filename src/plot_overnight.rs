@@ -0,0 +1,51 @@
+use crate::analytics;
+use crate::config::Config;
+use crate::model::FsLibreLine;
+use crate::overnight;
+use crate::variability;
+
+use chrono::Duration;
+use plotters::prelude::*;
+
+/// Plots the glucose of every undisturbed night of the period over the hours from midnight,
+/// headed by the mean change, slope and the assessment of the slow insulin.
+pub fn plot_overnight(data :&[FsLibreLine], path :&str, config :&Config) -> Result<(), Box<dyn std::error::Error>> {
+    let nights = overnight::undisturbed_nights(data, config);
+    let assessment = match overnight::assess_basal(&nights) {
+        Some(assessment) => assessment,
+        None => return Err(Box::from("no nights without food and fast acting insulin")),
+    };
+    let y_max = nights.iter().flat_map(|night| night.trace.iter().map(|reading| reading.1)).fold(250.0, f64::max).ceil();
+
+    let root = BitMapBackend::new(path, (800, 500)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let title = format!("nights {} - {}", data[0].timestamp.format("%Y-%m-%d"), data[data.len() - 1].timestamp.format("%Y-%m-%d"));
+    let root = root.titled(&title, ("sans-serif", 30.0).into_font())?;
+    let (header, lower) = root.split_vertically(45);
+    let mean_of = |value :&dyn Fn(&overnight::Night) -> f64| variability::mean(&nights.iter().map(value).collect::<Vec<f64>>());
+    header.draw(&Text::new(format!("{} nights, mean change {:+.0} mg/dL, slope {:+.1} mg/dL/h, dawn rise {:.0} mg/dL",
+        nights.len(), mean_of(&|night| night.change()), mean_of(&|night| night.slope), mean_of(&|night| night.dawn_rise)),
+        (60, 0), ("sans-serif", 16).into_font()))?;
+    header.draw(&Text::new(assessment.describe(), (60, 20), ("sans-serif", 16).into_font()))?;
+
+    let mut chart = ChartBuilder::on(&lower)
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .build_cartesian_2d(Duration::zero()..Duration::hours(overnight::NIGHT_END_HOUR as i64), 0.0..y_max)?;
+    chart.configure_mesh()
+        .light_line_style(&WHITE)
+        .x_labels(overnight::NIGHT_END_HOUR as usize + 1)
+        .x_label_formatter(&|x| format!("{}:00", x.num_hours()))
+        .y_label_formatter(&|y| format!("{:.0}", y))
+        .y_desc("mg/dL")
+        .draw()?;
+    chart.draw_series(std::iter::once(Rectangle::new(
+        [(Duration::zero(), analytics::LOW_LIMIT as f64), (Duration::hours(overnight::NIGHT_END_HOUR as i64), analytics::HIGH_LIMIT as f64)],
+        BLUE.mix(0.1).filled())))?;
+    for (index, night) in nights.iter().enumerate() {
+        let color = HSLColor(0.8 * index as f64 / nights.len() as f64, 0.8, 0.45);
+        chart.draw_series(LineSeries::new(night.trace.clone(), &color.mix(0.7)))?;
+    }
+    Ok(())
+}
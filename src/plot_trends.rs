@@ -0,0 +1,191 @@
+use crate::analytics;
+use crate::config::Config;
+use crate::insulin;
+use crate::model::FsLibreLine;
+use crate::risk;
+use crate::variability;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use plotters::prelude::*;
+
+/// Plots LBGI and HBGI (left axis) and the GRI (right axis) of every week of the period.
+pub fn plot_risk_trend(data :&[FsLibreLine], path :&str, config :&Config) -> Result<(), Box<dyn std::error::Error>> {
+    let mut labels = Vec::new();
+    let mut lbgi = Vec::new();
+    let mut hbgi = Vec::new();
+    let mut gri = Vec::new();
+    for week in analytics::split_weeks(data) {
+        if let Some(risk) = risk::glycemic_risk(week, config) {
+            let index = labels.len() as i32;
+            labels.push(week[0].timestamp.format("%Y-%m-%d").to_string());
+            lbgi.push((index, risk.lbgi));
+            hbgi.push((index, risk.hbgi));
+            gri.push((index, risk.gri));
+        }
+    }
+    if labels.is_empty() {
+        return Err(Box::from("no readings for a risk trend"));
+    }
+    // one empty slot on both sides keeps the first and last week off the axes
+    let index_range = -1..labels.len() as i32;
+    let bgi_max = lbgi.iter().chain(hbgi.iter()).map(|point| point.1).fold(5.0, f64::max) * 1.2;
+
+    let root = BitMapBackend::new(path, (800, 500)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("glycemic risk per week", ("sans-serif", 30.0).into_font())
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .right_y_label_area_size(50)
+        .build_cartesian_2d(index_range.clone(), 0.0..bgi_max)?
+        .set_secondary_coord(index_range, 0.0..100.0);
+
+    chart.configure_mesh()
+        .light_line_style(&WHITE)
+        .x_labels(labels.len() + 2)
+        .x_label_formatter(&|index| labels.get(*index as usize).cloned().unwrap_or_default())
+        .y_desc("LBGI / HBGI")
+        .draw()?;
+    chart.configure_secondary_axes()
+        .y_desc("GRI")
+        .draw()?;
+
+    chart.draw_series(LineSeries::new(lbgi.clone(), &RED))?
+        .label("LBGI")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &RED));
+    chart.draw_series(lbgi.iter().map(|point| Circle::new(*point, 3, RED.filled())))?;
+    chart.draw_series(LineSeries::new(hbgi.clone(), &RGBColor(255, 140, 0)))?
+        .label("HBGI")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &RGBColor(255, 140, 0)));
+    chart.draw_series(hbgi.iter().map(|point| Circle::new(*point, 3, RGBColor(255, 140, 0).filled())))?;
+    chart.draw_secondary_series(LineSeries::new(gri.clone(), &BLACK))?
+        .label("GRI")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &BLACK));
+    chart.draw_secondary_series(gri.iter().map(|point| Circle::new(*point, 3, BLACK.filled())))?;
+
+    chart.configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(&WHITE.mix(0.8))
+        .border_style(&BLACK)
+        .draw()?;
+
+    Ok(())
+}
+
+/// Statistics of one day or week of the trend chart.
+struct TrendPoint {
+    /// The day, or the Monday of the week.
+    start :NaiveDate,
+    mean :Option<f64>,
+    time_in_range :Option<f64>,
+    cv :Option<f64>,
+    /// Per day, averaged over the days of a week.
+    insulin :f64,
+    carbs :f64,
+}
+
+/// Plots mean glucose, time in range, CV, total insulin and carbs of every day (or week) of the period
+/// in five panels below each other, each with a trailing moving average over `window` calendar days (or weeks).
+/// Days without data are left out of the average instead of shifting the window further back.
+pub fn plot_statistics_trend(data :&[FsLibreLine], path :&str, weekly :bool, window :usize, config :&Config) -> Result<(), Box<dyn std::error::Error>> {
+    let parts = if weekly {analytics::split_weeks(data)} else {analytics::split_days(data)};
+    let points :Vec<TrendPoint> = parts.iter().map(|part| trend_point(part, weekly, config)).collect();
+    if points.is_empty() {
+        return Err(Box::from("no readings for a trend"));
+    }
+    let labels :Vec<String> = points.iter().map(|point| point.start.format("%Y-%m-%d").to_string()).collect();
+    let dates :Vec<NaiveDate> = points.iter().map(|point| point.start).collect();
+    let window_span = Duration::days(window as i64 * if weekly {7} else {1});
+    let values = |value :&dyn Fn(&TrendPoint) -> Option<f64>| -> Vec<(i32, f64)> {
+        points.iter().enumerate().filter_map(|(index, point)| value(point).map(|value| (index as i32, value))).collect()
+    };
+    let panels = [
+        ("mean glucose", "mg/dL", values(&|point| point.mean), RGBColor(70, 130, 180)),
+        ("time in range", "%", values(&|point| point.time_in_range.map(|share| share * 100.0)), RGBColor(34, 139, 34)),
+        ("coefficient of variation", "%", values(&|point| point.cv), RGBColor(128, 0, 128)),
+        ("insulin per day", "U", values(&|point| Some(point.insulin)), RGBColor(30, 144, 255)),
+        ("carbs per day", "g", values(&|point| Some(point.carbs)), RGBColor(255, 140, 0)),
+    ];
+
+    let root = BitMapBackend::new(path, (1100, 1100)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let title = format!("{} statistics {} - {}, line: {} {} moving average", if weekly {"weekly"} else {"daily"},
+        data[0].timestamp.format("%Y-%m-%d"), data[data.len() - 1].timestamp.format("%Y-%m-%d"), window, if weekly {"week"} else {"day"});
+    let root = root.titled(&title, ("sans-serif", 30.0).into_font())?;
+    let areas = root.split_evenly((panels.len(), 1));
+    let index_range = -1..labels.len() as i32;
+    for (number, (area, (name, unit, values, color))) in areas.iter().zip(panels.iter()).enumerate() {
+        let last = number == panels.len() - 1;
+        let y_max = if *unit == "%" && name.starts_with("time") {100.0} else {values.iter().map(|point| point.1).fold(1.0, f64::max) * 1.1};
+        let mut chart = ChartBuilder::on(area)
+            .caption(format!("{} ({})", name, unit), ("sans-serif", 16.0).into_font())
+            .margin(5)
+            .x_label_area_size(if last {40} else {0})
+            .y_label_area_size(60)
+            .build_cartesian_2d(index_range.clone(), 0.0..y_max)?;
+        chart.configure_mesh()
+            .light_line_style(&WHITE)
+            .x_labels(labels.len().min(10) + 2)
+            .x_label_formatter(&|index| labels.get(*index as usize).cloned().unwrap_or_default())
+            .y_labels(5)
+            .y_label_formatter(&|y| format!("{:.0}", y))
+            .draw()?;
+
+        chart.draw_series(values.iter().map(|point| Circle::new(*point, 2, color.filled())))?;
+        chart.draw_series(LineSeries::new(values.clone(), &color.mix(0.3)))?;
+        chart.draw_series(LineSeries::new(moving_average(values, &dates, window_span), color.stroke_width(2)))?;
+    }
+    Ok(())
+}
+
+fn trend_point(part :&[FsLibreLine], weekly :bool, config :&Config) -> TrendPoint {
+    let variability = variability::variability(part, config);
+    let days = analytics::period_days(part) as f64;
+    let date = part[0].timestamp.date();
+    TrendPoint {
+        start: if weekly {date - Duration::days(date.weekday().num_days_from_monday() as i64)} else {date},
+        mean: variability.as_ref().map(|variability| variability.mean),
+        time_in_range: analytics::time_in_range(part, config).map(|tir| tir.in_range),
+        cv: variability.as_ref().map(|variability| variability.cv),
+        insulin: insulin::insulin_dose(part).daily_total(),
+        carbs: analytics::total_carbs(part) as f64 / days,
+    }
+}
+
+/// Mean of every point and the points dated less than `window` before it, `dates` are the dates of the indices.
+fn moving_average(values :&[(i32, f64)], dates :&[NaiveDate], window :Duration) -> Vec<(i32, f64)> {
+    values.iter()
+        .map(|(index, _)| {
+            let date = dates[*index as usize];
+            let in_window :Vec<f64> = values.iter()
+                .filter(|other| dates[other.0 as usize] <= date && dates[other.0 as usize] > date - window)
+                .map(|other| other.1)
+                .collect();
+            (*index, variability::mean(&in_window))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_average_window_is_calendar_days() {
+        // days 1, 2 and 3 of January, then nothing until the 10th
+        let dates :Vec<NaiveDate> = [1, 2, 3, 10, 11].iter().map(|day| NaiveDate::from_ymd(2021, 1, *day)).collect();
+        let values = vec![(0, 100.0), (1, 110.0), (2, 120.0), (3, 200.0), (4, 210.0)];
+        let average = moving_average(&values, &dates, Duration::days(3));
+        assert_eq!(average, vec![(0, 100.0), (1, 105.0), (2, 110.0), (3, 200.0), (4, 205.0)]);
+    }
+
+    #[test]
+    fn moving_average_leaves_out_days_without_a_value() {
+        let dates :Vec<NaiveDate> = (1..=3).map(|day| NaiveDate::from_ymd(2021, 1, day)).collect();
+        // no value on the 2nd
+        let values = vec![(0, 100.0), (2, 120.0)];
+        assert_eq!(moving_average(&values, &dates, Duration::days(7)), vec![(0, 100.0), (2, 110.0)]);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::analytics;
+use crate::artefacts;
+use crate::config::Config;
+use crate::episodes;
+use crate::model::FsLibreLine;
+use crate::on_board;
+use crate::variability;
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+
+/// Insulin given before this is taken as still acting at a dose if more than this is on board.
+const MAX_INSULIN_ON_BOARD :f64 = 0.5;
+/// Carbs still being absorbed at a correction make it unusable for the correction factor.
+const MAX_CARBS_ON_BOARD :f64 = 1.0;
+/// A meal shortly before another one makes both unusable for the carb ratio.
+const MEAL_SEPARATION_MINUTES :i64 = 60;
+/// Meals whose glucose came back this close to the glucose before need no correction factor for the carb ratio.
+const BALANCED_MEAL_DELTA :f64 = 30.0;
+
+/// Parts of the day the ratios are estimated for.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimeBlock {
+    Night,
+    Morning,
+    Midday,
+    Evening,
+}
+
+impl TimeBlock {
+    pub const ALL :[TimeBlock; 4] = [TimeBlock::Night, TimeBlock::Morning, TimeBlock::Midday, TimeBlock::Evening];
+
+    pub fn of(time :NaiveDateTime) -> TimeBlock {
+        match time.hour() {
+            0..=5 => TimeBlock::Night,
+            6..=10 => TimeBlock::Morning,
+            11..=16 => TimeBlock::Midday,
+            _ => TimeBlock::Evening,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimeBlock::Night => "00-06",
+            TimeBlock::Morning => "06-11",
+            TimeBlock::Midday => "11-17",
+            TimeBlock::Evening => "17-24",
+        }
+    }
+}
+
+/// Mean of a sample with its 95% confidence interval.
+pub struct Estimate {
+    pub mean :f64,
+    pub low :f64,
+    pub high :f64,
+    pub count :usize,
+}
+
+/// Estimated ratios of one time block, `None` if there were less than two usable events.
+pub struct BlockRatios {
+    pub block :TimeBlock,
+    /// Grams of carbohydrates covered by one unit of fast acting insulin.
+    pub carb_ratio :Option<Estimate>,
+    /// Drop in mg/dL caused by one unit of fast acting insulin.
+    pub correction_factor :Option<Estimate>,
+}
+
+/// Estimates the insulin-to-carb ratio and the correction factor of every time block from the
+/// logged doses and the glucose before and after them, once the insulin has acted
+/// (`config.insulin_duration`). Events are left out if other meals or doses overlap them,
+/// if glucose fell below `config.hypo_limit` or if readings are missing.
+/// These are retrospective estimates of what the doses did, not dosing advice.
+pub fn estimate_ratios(data :&[FsLibreLine], config :&Config) -> Vec<BlockRatios> {
+    let data = &artefacts::without_compression_lows(data, config);
+    let corrections = correction_factors(data, config);
+    let all_factors :Vec<f64> = corrections.iter().map(|event| event.1).collect();
+    let meals = carb_ratios(data, config, &corrections, &all_factors);
+    TimeBlock::ALL.iter()
+        .map(|block| BlockRatios {
+            block: *block,
+            carb_ratio: estimate(&values_of(&meals, *block)),
+            correction_factor: estimate(&values_of(&corrections, *block)),
+        })
+        .collect()
+}
+
+fn values_of(events :&[(TimeBlock, f64)], block :TimeBlock) -> Vec<f64> {
+    events.iter().filter(|event| event.0 == block).map(|event| event.1).collect()
+}
+
+/// Correction doses: fast acting insulin without carbs on board, without other insulin
+/// acting and without a meal before the dose has acted.
+fn correction_factors(data :&[FsLibreLine], config :&Config) -> Vec<(TimeBlock, f64)> {
+    let series = analytics::glucose_series(data);
+    let insulin_profile = config.insulin_profile();
+    let carb_profile = config.carb_profile();
+    let action = Duration::minutes(config.insulin_duration);
+    let mut factors = Vec::new();
+    for dose in data.iter().filter(|line| line.fast_insulin_value() > 0) {
+        let end = dose.timestamp + action;
+        let earlier = &data[..data.partition_point(|line| line.timestamp < dose.timestamp)];
+        let confounded = on_board::insulin_on_board(earlier, &insulin_profile, dose.timestamp) > MAX_INSULIN_ON_BOARD
+            || on_board::carbs_on_board(data, &carb_profile, dose.timestamp) > MAX_CARBS_ON_BOARD
+            || data.iter().any(|line| line.timestamp >= dose.timestamp && line.timestamp <= end
+                && (line.food_value() > 0 || (line.fast_insulin_value() > 0 && line.timestamp != dose.timestamp)));
+        if confounded || had_hypo(&series, dose.timestamp, end, config) {
+            continue;
+        }
+        if let (Some(before), Some(after)) = (variability::value_at(&series, dose.timestamp), variability::value_at(&series, end)) {
+            factors.push((TimeBlock::of(dose.timestamp), (before - after) / dose.fast_insulin_value() as f64));
+        }
+    }
+    factors
+}
+
+/// Meals with a bolus given for them and no other meal or dose overlapping. The part of the bolus
+/// that corrected (or failed to correct) the glucose change from before the meal to after the
+/// insulin has acted is taken off with the correction factor of the block, or of the period if the
+/// block has none. Without any correction factor only meals with glucose back near the start are used.
+fn carb_ratios(data :&[FsLibreLine], config :&Config, corrections :&[(TimeBlock, f64)], all_factors :&[f64]) -> Vec<(TimeBlock, f64)> {
+    let series = analytics::glucose_series(data);
+    let insulin_profile = config.insulin_profile();
+    let action = Duration::minutes(config.insulin_duration);
+    let bolus_window = Duration::minutes(episodes::MEAL_BOLUS_MINUTES);
+    let mut ratios = Vec::new();
+    for meal in data.iter().filter(|line| line.food_value() > 0) {
+        let start = meal.timestamp - bolus_window;
+        let end = meal.timestamp + action;
+        let is_meal_bolus = |line :&FsLibreLine| (line.timestamp - meal.timestamp).num_seconds().abs() <= bolus_window.num_seconds();
+        let bolus :u32 = data.iter().filter(|line| is_meal_bolus(line)).map(|line| line.fast_insulin_value()).sum();
+        if bolus == 0 {
+            continue;
+        }
+        let earlier = &data[..data.partition_point(|line| line.timestamp < start)];
+        let confounded = on_board::insulin_on_board(earlier, &insulin_profile, start) > MAX_INSULIN_ON_BOARD
+            || data.iter().any(|line| line.timestamp > meal.timestamp - Duration::minutes(MEAL_SEPARATION_MINUTES) && line.timestamp < end
+                && ((line.food_value() > 0 && line.timestamp != meal.timestamp) || (line.fast_insulin_value() > 0 && !is_meal_bolus(line))));
+        if confounded || had_hypo(&series, meal.timestamp, end, config) {
+            continue;
+        }
+        let (before, after) = match (variability::value_at(&series, meal.timestamp), variability::value_at(&series, end)) {
+            (Some(before), Some(after)) => (before, after),
+            _ => continue,
+        };
+        let block = TimeBlock::of(meal.timestamp);
+        let block_factors = values_of(corrections, block);
+        let factor = if block_factors.is_empty() {all_factors} else {&block_factors[..]};
+        let needed_units = if factor.is_empty() || variability::mean(factor) <= 0.0 {
+            if (after - before).abs() > BALANCED_MEAL_DELTA {
+                continue;
+            }
+            bolus as f64
+        } else {
+            bolus as f64 + (after - before) / variability::mean(factor)
+        };
+        if needed_units > 0.0 {
+            ratios.push((block, meal.food_value() as f64 / needed_units));
+        }
+    }
+    ratios
+}
+
+fn had_hypo(series :&[(NaiveDateTime, f64)], start :NaiveDateTime, end :NaiveDateTime, config :&Config) -> bool {
+    series.iter().any(|reading| reading.0 >= start && reading.0 <= end && reading.1 < config.hypo_limit as f64)
+}
+
+/// Mean with its 95% confidence interval after Student's t, `None` for less than two values.
+fn estimate(values :&[f64]) -> Option<Estimate> {
+    if values.len() < 2 {
+        return None;
+    }
+    let mean = variability::mean(values);
+    let margin = t_quantile(values.len() - 1) * variability::standard_deviation(values) / (values.len() as f64).sqrt();
+    Some(Estimate {mean, low: mean - margin, high: mean + margin, count: values.len()})
+}
+
+/// Two-sided 95% quantile of Student's t distribution.
+fn t_quantile(degrees_of_freedom :usize) -> f64 {
+    const QUANTILES :[f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+        2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+        2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+    ];
+    QUANTILES.get(degrees_of_freedom - 1).copied().unwrap_or(1.96)
+}
@@ -0,0 +1,429 @@
+use crate::analytics;
+use crate::artefacts::{self, Artefact};
+use crate::config::Config;
+use crate::episodes::{self, Episode, HyperCause};
+use crate::insulin;
+use crate::meals::{self, MealResponse, MealSlot};
+use crate::model::FsLibreLine;
+use crate::overnight;
+use crate::pdf;
+use crate::ratios::{self, Estimate};
+use crate::risk;
+use crate::sessions::{self, Session};
+use crate::variability::{self, Variability};
+
+use std::fs;
+
+const RATIOS_TITLE :&str = "insulin-to-carb ratio and correction factor (retrospective estimates, mean and 95% confidence interval)";
+const NO_DATA :&str = "no readings in the selected period";
+const RATIOS_NOTE :&str = "estimated from logged doses and glucose, not a dosing recommendation - discuss with your care team before changing any settings";
+
+pub fn print_day(title :&str, data_of_day :&[FsLibreLine], config :&Config) {
+    for line in day_lines(title, data_of_day, config) {
+        println!("{}", line);
+    }
+}
+
+/// Time in range, coverage, variability and insulin of one day.
+fn day_lines(title :&str, data_of_day :&[FsLibreLine], config :&Config) -> Vec<String> {
+    let mut lines = Vec::new();
+    match analytics::time_in_range(data_of_day, config) {
+        Some(tir) => lines.push(format!("{}: very low {} | low {} | in range {} | high {} | very high {}",
+            title, percent(tir.very_low), percent(tir.low), percent(tir.in_range), percent(tir.high), percent(tir.very_high))),
+        None => lines.push(format!("{}: no historic readings", title)),
+    }
+    lines.push(format!("    sensor coverage {}", percent(analytics::sensor_coverage(data_of_day))));
+    if let Some(variability) = variability::variability(data_of_day, config) {
+        lines.push(format!("    mean {:.0} | SD {:.1} | CV {:.1}% | MAGE {} | CONGA{} {} | J-index {:.1}",
+            variability.mean, variability.sd, variability.cv, optional(variability.mage),
+            config.conga_hours, optional(variability.conga), variability.j_index));
+    }
+    lines.push(format!("    insulin {}", insulin::describe(&insulin::insulin_dose(data_of_day))));
+    lines
+}
+
+pub fn print_period(data :&[FsLibreLine], config :&Config) {
+    if data.is_empty() {
+        return;
+    }
+    println!();
+    for line in period_lines(data, config) {
+        println!("{}", line);
+    }
+}
+
+/// The summary of the period followed by the lists of episodes, artefacts, sessions, meals and ratios.
+fn period_lines(data :&[FsLibreLine], config :&Config) -> Vec<String> {
+    let mut lines = vec![format!("{}:", period_title(data))];
+    for (name, value) in summary(data, config) {
+        lines.push(format!("    {}: {}", name, value));
+    }
+
+    let hypos = episodes::hypo_episodes(data, config.hypo_limit, config);
+    if !hypos.is_empty() {
+        lines.push(String::new());
+        lines.push(format!("hypoglycemia episodes (below {} mg/dL):", config.hypo_limit));
+        for episode in &hypos {
+            lines.push(format!("    {}", episode_columns(episode, "nadir").join(" | ")));
+        }
+    }
+
+    let hypers = episodes::hyper_episodes(data, config.hyper_limit, config);
+    if !hypers.is_empty() {
+        lines.push(String::new());
+        lines.push(format!("hyperglycemia episodes (above {} mg/dL):", config.hyper_limit));
+        let very_high = episodes::hyper_episodes(data, analytics::VERY_HIGH_LIMIT, config);
+        for episode in &hypers {
+            lines.push(format!("    {}", hyper_columns(data, episode, &very_high).join(" | ")));
+        }
+    }
+
+    let artefacts = artefacts::detect_artefacts(data, config);
+    if !artefacts.is_empty() {
+        lines.push(String::new());
+        lines.push(String::from("probable sensor artefacts:"));
+        for artefact in &artefacts {
+            lines.push(format!("    {}", artefact_columns(artefact).join(" | ")));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push(String::from("sensor sessions:"));
+    for session in sessions::sensor_sessions(data) {
+        lines.push(format!("    {}", session_columns(&session).join(" | ")));
+    }
+
+    let responses = meals::meal_responses(data, config);
+    if !responses.is_empty() {
+        lines.push(String::new());
+        lines.push(format!("glucose after meals (up to {} hours):", meals::RESPONSE_HOURS));
+        for response in &responses {
+            lines.push(format!("    {}", meal_columns(response).join(" | ")));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push(format!("{}:", RATIOS_TITLE));
+    for block_ratios in ratios::estimate_ratios(data, config) {
+        lines.push(format!("    {} | carb ratio {} | correction factor {}", block_ratios.block.name(),
+            estimate_text(&block_ratios.carb_ratio, "g/U"), estimate_text(&block_ratios.correction_factor, "mg/dL/U")));
+    }
+    lines.push(format!("    {}", RATIOS_NOTE));
+    lines
+}
+
+/// Writes the statistics of the period and of every single day as a printable PDF document.
+pub fn write_pdf(path :&str, data :&[FsLibreLine], days :&[Vec<FsLibreLine>], config :&Config) -> std::io::Result<()> {
+    if data.is_empty() {
+        return pdf::write_text(path, &[String::from(NO_DATA)]);
+    }
+    let mut lines = vec![format!("FreeStyle Libre {}", period_title(data)), String::new()];
+    lines.extend(period_lines(data, config));
+    lines.push(String::new());
+    lines.push(String::from("days:"));
+    for data_of_day in days {
+        lines.extend(day_lines(&data_of_day[0].timestamp.format("%Y-%m-%d").to_string(), data_of_day, config));
+    }
+    pdf::write_text(path, &lines)
+}
+
+/// Writes the statistics of the period and of every single day as a HTML page,
+/// linking the daily plots.
+pub fn write_html(path :&str, data :&[FsLibreLine], days :&[Vec<FsLibreLine>], config :&Config) -> std::io::Result<()> {
+    if data.is_empty() {
+        return fs::write(path, format!("<!DOCTYPE html>\n<html>\n<body>\n<p>{}</p>\n</body>\n</html>\n", NO_DATA));
+    }
+    let title = period_title(data);
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>FreeStyle Libre {}</title>\n", title));
+    html.push_str("<style>body {font-family: sans-serif;} td, th {padding: 2px 10px; text-align: right;} th {background: #eee;}</style>\n");
+    html.push_str("</head>\n<body>\n");
+    html.push_str(&format!("<h1>{}</h1>\n", title));
+
+    html.push_str("<h2>Summary</h2>\n<table>\n");
+    for (name, value) in summary(data, config) {
+        html.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", escape_html(&name), escape_html(&value)));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Days</h2>\n<table>\n");
+    html.push_str(&format!("<tr><th>day</th><th>&lt;54</th><th>54-69</th><th>70-180</th><th>181-250</th><th>&gt;250</th><th>mean</th><th>SD</th><th>CV</th><th>MAGE</th><th>CONGA{}</th><th>insulin</th><th>basal</th><th>coverage</th><th>plot</th></tr>\n", config.conga_hours));
+    for data_of_day in days {
+        let date = data_of_day[0].timestamp.format("%Y-%m-%d").to_string();
+        html.push_str(&format!("<tr><td>{}</td>", date));
+        match analytics::time_in_range(data_of_day, config) {
+            Some(tir) => {
+                for share in [tir.very_low, tir.low, tir.in_range, tir.high, tir.very_high].iter() {
+                    html.push_str(&format!("<td>{}</td>", percent(*share)));
+                }
+            }
+            None => html.push_str("<td colspan=\"5\">no historic readings</td>"),
+        }
+        match variability::variability(data_of_day, config) {
+            Some(variability) => html.push_str(&format!("<td>{:.0}</td><td>{:.1}</td><td>{:.1}%</td><td>{}</td><td>{}</td>",
+                variability.mean, variability.sd, variability.cv, optional(variability.mage), optional(variability.conga))),
+            None => html.push_str("<td colspan=\"5\"></td>"),
+        }
+        let dose = insulin::insulin_dose(data_of_day);
+        html.push_str(&format!("<td>{} U</td><td>{}</td>", dose.total(), dose.basal_share().map(percent).unwrap_or_else(|| String::from("-"))));
+        html.push_str(&format!("<td>{}</td>", percent(analytics::sensor_coverage(data_of_day))));
+        html.push_str(&format!("<td><a href=\"{}.png\">{}.png</a></td></tr>\n", date, date));
+    }
+    html.push_str("</table>\n");
+
+    let artefacts = artefacts::detect_artefacts(data, config);
+    html.push_str("<h2>Probable sensor artefacts</h2>\n");
+    if artefacts.is_empty() {
+        html.push_str("<p>none</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>time</th><th>kind</th><th>glucose</th></tr>\n");
+        for artefact in &artefacts {
+            html.push_str("<tr>");
+            for column in artefact_columns(artefact) {
+                html.push_str(&format!("<td>{}</td>", column));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Sensor sessions</h2>\n<table>\n<tr><th>time</th><th>worn</th><th>coverage</th><th>warm-up</th><th>end</th></tr>\n");
+    for session in sessions::sensor_sessions(data) {
+        html.push_str("<tr>");
+        for column in session_columns(&session) {
+            html.push_str(&format!("<td>{}</td>", column));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+
+    let hypos = episodes::hypo_episodes(data, config.hypo_limit, config);
+    html.push_str(&format!("<h2>Hypoglycemia episodes (below {} mg/dL)</h2>\n", config.hypo_limit));
+    write_html_episodes(&mut html, &hypos, "nadir");
+
+    let hypers = episodes::hyper_episodes(data, config.hyper_limit, config);
+    html.push_str(&format!("<h2>Hyperglycemia episodes (above {} mg/dL)</h2>\n", config.hyper_limit));
+    if hypers.is_empty() {
+        html.push_str("<p>none</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>time</th><th>duration</th><th>peak</th><th>recovery</th><th>night</th><th>area</th><th>level</th><th>preceding meal</th></tr>\n");
+        let very_high = episodes::hyper_episodes(data, analytics::VERY_HIGH_LIMIT, config);
+        for episode in &hypers {
+            html.push_str("<tr>");
+            for column in hyper_columns(data, episode, &very_high) {
+                html.push_str(&format!("<td>{}</td>", column));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+
+    let responses = meals::meal_responses(data, config);
+    html.push_str(&format!("<h2>Glucose after meals (up to {} hours)</h2>\n", meals::RESPONSE_HOURS));
+    if responses.is_empty() {
+        html.push_str("<p>none</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>time</th><th>meal</th><th>carbs</th><th>before</th><th>peak</th><th>time to peak</th><th>rise</th><th>iAUC</th></tr>\n");
+        for response in &responses {
+            html.push_str("<tr>");
+            for column in meal_columns(response) {
+                html.push_str(&format!("<td>{}</td>", column));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str(&format!("<h2>{}</h2>\n<table>\n", escape_html(RATIOS_TITLE)));
+    html.push_str("<tr><th>time</th><th>carb ratio</th><th>correction factor</th></tr>\n");
+    for block_ratios in ratios::estimate_ratios(data, config) {
+        html.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n", block_ratios.block.name(),
+            estimate_text(&block_ratios.carb_ratio, "g/U"), estimate_text(&block_ratios.correction_factor, "mg/dL/U")));
+    }
+    html.push_str(&format!("</table>\n<p>{}</p>\n", escape_html(RATIOS_NOTE)));
+
+    html.push_str("</body>\n</html>\n");
+    fs::write(path, html)
+}
+
+fn write_html_episodes(html :&mut String, episodes :&[Episode], extreme_name :&str) {
+    if episodes.is_empty() {
+        html.push_str("<p>none</p>\n");
+        return;
+    }
+    html.push_str(&format!("<table>\n<tr><th>time</th><th>duration</th><th>{}</th><th>recovery</th><th>night</th></tr>\n", extreme_name));
+    for episode in episodes {
+        html.push_str("<tr>");
+        for column in episode_columns(episode, extreme_name) {
+            html.push_str(&format!("<td>{}</td>", column));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+}
+
+/// Time span, duration, nadir or peak, recovery time and whether it was at night.
+fn episode_columns(episode :&Episode, extreme_name :&str) -> Vec<String> {
+    vec![
+        format!("{} - {}", episode.start.format("%Y-%m-%d %H:%M"), episode.end.format("%H:%M")),
+        format!("{} min", episode.duration().num_minutes()),
+        format!("{} {:.0} at {}", extreme_name, episode.extreme, episode.extreme_time.format("%H:%M")),
+        format!("recovery {} min", episode.recovery().num_minutes()),
+        String::from(if episode.nocturnal() {"nocturnal"} else {"day"}),
+    ]
+}
+
+/// The episode columns plus area above the threshold, level and the probable cause.
+/// `very_high` are the episodes above 250 mg/dL, an episode overlapping one of them is level 2.
+fn hyper_columns(data :&[FsLibreLine], episode :&Episode, very_high :&[Episode]) -> Vec<String> {
+    let mut columns = episode_columns(episode, "peak");
+    columns.push(format!("area {:.0} mg/dL*min", episode.area));
+    let level_2 = very_high.iter().any(|high| high.start <= episode.end && episode.start <= high.end);
+    columns.push(String::from(if level_2 {"level 2"} else {"level 1"}));
+    columns.push(match episodes::hyper_cause(data, episode) {
+        HyperCause::Meal {time, carbs, bolus: Some(bolus)} => format!("meal {}g at {} with {}U bolus", carbs, time.format("%H:%M"), bolus),
+        HyperCause::Meal {time, carbs, bolus: None} => format!("meal {}g at {} without bolus", carbs, time.format("%H:%M")),
+        HyperCause::NoMeal => String::from("no meal before"),
+    });
+    columns
+}
+
+/// Time span, kind and nadir (or glucose after the jump).
+fn artefact_columns(artefact :&Artefact) -> Vec<String> {
+    vec![
+        format!("{} - {}", artefact.start.format("%Y-%m-%d %H:%M"), artefact.end.format("%H:%M")),
+        artefact.kind.name().to_string(),
+        format!("{:.0} mg/dL", artefact.glucose),
+    ]
+}
+
+/// Time span, wear duration, coverage, warm-up and whether the sensor failed early.
+fn session_columns(session :&Session) -> Vec<String> {
+    vec![
+        format!("{} - {}", session.start.format("%Y-%m-%d %H:%M"), session.last_reading.format("%Y-%m-%d %H:%M")),
+        format!("{:.1} days", session.wear_duration().num_minutes() as f64 / (24.0 * 60.0)),
+        format!("coverage {}", percent(session.coverage)),
+        if session.started_by_record {format!("warm-up {} min", session.warm_up().num_minutes())} else {String::from("no start record")},
+        String::from(if session.early_failure {"failed early"} else {"ok"}),
+    ]
+}
+
+/// Time, slot, carbs, glucose before, peak, time to peak, rise and incremental area.
+fn meal_columns(response :&MealResponse) -> Vec<String> {
+    vec![
+        response.time.format("%Y-%m-%d %H:%M").to_string(),
+        response.slot.name().to_string(),
+        format!("{}g", response.carbs),
+        format!("before {:.0}", response.baseline),
+        format!("peak {:.0}", response.peak),
+        format!("after {} min", response.time_to_peak.num_minutes()),
+        format!("rise {:+.0}", response.delta),
+        format!("iAUC {:.0} mg/dL*min", response.iauc),
+    ]
+}
+
+fn estimate_text(estimate :&Option<Estimate>, unit :&str) -> String {
+    match estimate {
+        Some(estimate) => format!("{:.1} {} ({:.1} - {:.1}, n={})", estimate.mean, unit, estimate.low, estimate.high, estimate.count),
+        None => String::from("too few events"),
+    }
+}
+
+fn period_title(data :&[FsLibreLine]) -> String {
+    format!("{} - {}",
+        data[0].timestamp.format("%Y-%m-%d"),
+        data[data.len() - 1].timestamp.format("%Y-%m-%d"))
+}
+
+/// Name and formatted value of every statistic of a period.
+fn summary(data :&[FsLibreLine], config :&Config) -> Vec<(String, String)> {
+    let mut rows = Vec::new();
+    if let Some(tir) = analytics::time_in_range(data, config) {
+        rows.push((String::from("time very low (<54)"), percent(tir.very_low)));
+        rows.push((String::from("time low (54-69)"), percent(tir.low)));
+        rows.push((String::from("time in range (70-180)"), percent(tir.in_range)));
+        rows.push((String::from("time high (181-250)"), percent(tir.high)));
+        rows.push((String::from("time very high (>250)"), percent(tir.very_high)));
+    }
+    if let Some(gmi) = analytics::glucose_management_indicator(data, config) {
+        rows.push((String::from("mean glucose"), format!("{:.0} mg/dL", gmi.mean_glucose)));
+        rows.push((String::from("GMI"), format!("{:.1}% / {:.0} mmol/mol", gmi.gmi_percent, gmi.gmi_mmol_mol)));
+        rows.push((String::from("estimated A1c"), format!("{:.1}% / {:.0} mmol/mol", gmi.ea1c_percent, gmi.ea1c_mmol_mol)));
+        rows.push((String::from("days"), gmi.days.to_string()));
+        rows.push((String::from("sensor coverage"), percent(gmi.coverage)));
+        if !gmi.sufficient {
+            rows.push((String::from("note"), format!("too little data for GMI, at least {} days with {} sensor coverage are recommended",
+                analytics::GMI_MIN_DAYS, percent(analytics::GMI_MIN_COVERAGE))));
+        }
+    }
+    if let Some(variability) = variability::variability(data, config) {
+        rows.extend(variability_rows(&variability, config));
+    }
+    let artefacts = artefacts::detect_artefacts(data, config);
+    rows.push((String::from("probable sensor artefacts"), format!("{} compression lows{}, {} jumps",
+        artefacts.iter().filter(|artefact| artefact.kind == artefacts::ArtefactKind::CompressionLow).count(),
+        if config.exclude_artefacts {" (left out of the statistics)"} else {""},
+        artefacts.iter().filter(|artefact| artefact.kind == artefacts::ArtefactKind::Jump).count())));
+    let sensors = sessions::sensor_sessions(data);
+    rows.push((String::from("sensor sessions"), format!("{} ({} failed early)", sensors.len(),
+        sensors.iter().filter(|session| session.early_failure).count())));
+    rows.push((String::from("total daily dose"), insulin::describe(&insulin::insulin_dose(data))));
+    let hypos = episodes::hypo_episodes(data, config.hypo_limit, config);
+    rows.push((format!("hypoglycemia episodes (<{})", config.hypo_limit),
+        format!("{} ({} nocturnal)", hypos.len(), hypos.iter().filter(|episode| episode.nocturnal()).count())));
+    let hypers = episodes::hyper_episodes(data, config.hyper_limit, config);
+    rows.push((format!("hyperglycemia episodes (>{})", config.hyper_limit), hypers.len().to_string()));
+    let responses = meals::meal_responses(data, config);
+    for slot in MealSlot::ALL.iter() {
+        let slot_responses :Vec<&MealResponse> = responses.iter().filter(|response| response.slot == *slot).collect();
+        if slot_responses.is_empty() {
+            continue;
+        }
+        let mean_of = |value :&dyn Fn(&MealResponse) -> f64| variability::mean(&slot_responses.iter().map(|response| value(response)).collect::<Vec<f64>>());
+        rows.push((format!("{} response", slot.name()), format!("{} meals, mean rise {:+.0} after {:.0} min, iAUC {:.0} mg/dL*min",
+            slot_responses.len(), mean_of(&|response| response.delta),
+            mean_of(&|response| response.time_to_peak.num_minutes() as f64), mean_of(&|response| response.iauc))));
+    }
+    let nights = overnight::undisturbed_nights(data, config);
+    if let Some(assessment) = overnight::assess_basal(&nights) {
+        let mean_of = |value :&dyn Fn(&overnight::Night) -> f64| variability::mean(&nights.iter().map(value).collect::<Vec<f64>>());
+        rows.push((format!("nights (00-{:02})", overnight::NIGHT_END_HOUR), format!("{} without food or bolus, mean change {:+.0} mg/dL, slope {:+.1} mg/dL/h, dawn rise over {:.0} on {}",
+            nights.len(), mean_of(&|night| night.change()), mean_of(&|night| night.slope), overnight::DAWN_RISE,
+            nights.iter().filter(|night| night.dawn_rise > overnight::DAWN_RISE).count())));
+        rows.push((String::from("slow insulin"), assessment.describe().to_string()));
+    }
+    if let Some(risk) = risk::glycemic_risk(data, config) {
+        rows.push((String::from("LBGI"), format!("{:.1}", risk.lbgi)));
+        rows.push((String::from("HBGI"), format!("{:.1}", risk.hbgi)));
+        rows.push((String::from("GRI"), format!("{:.1} (hypo {:.1}, hyper {:.1})", risk.gri, risk.gri_hypo, risk.gri_hyper)));
+    }
+    rows
+}
+
+fn variability_rows(variability :&Variability, config :&Config) -> Vec<(String, String)> {
+    vec![
+        (String::from("standard deviation"), format!("{:.1} mg/dL", variability.sd)),
+        (String::from("coefficient of variation"), format!("{:.1}%", variability.cv)),
+        (String::from("MAGE"), optional(variability.mage)),
+        (format!("CONGA{}", config.conga_hours), optional(variability.conga)),
+        (String::from("MODD"), optional(variability.modd)),
+        (String::from("J-index"), format!("{:.1}", variability.j_index)),
+    ]
+}
+
+/// A mg/dL value that cannot always be computed.
+fn optional(value :Option<f64>) -> String {
+    match value {
+        Some(value) => format!("{:.1}", value),
+        None => String::from("-"),
+    }
+}
+
+fn escape_html(text :&str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn percent(share :f64) -> String {
+    format!("{:.1}%", share * 100.0)
+}
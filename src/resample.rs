@@ -0,0 +1,172 @@
+use crate::analytics;
+use crate::artefacts;
+use crate::config::{Config, Interpolation};
+use crate::model::FsLibreLine;
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+
+/// One point of a regular time grid.
+#[derive(Clone, Copy, Debug)]
+pub struct GridPoint {
+    pub time :NaiveDateTime,
+    /// `None` where there is neither a reading nor an interpolated value.
+    pub glucose :Option<f64>,
+    /// Whether `glucose` was interpolated between the readings around the point.
+    pub interpolated :bool,
+}
+
+/// Puts the readings on a grid of `step_minutes` starting at full hours: every grid point takes the
+/// reading closest to it if there is one within half a step. Points without are filled in by
+/// `interpolation` between the readings before and after, with `Interpolation::LimitedLinear`
+/// only if those are at most `max_gap` apart.
+pub fn resample(series :&[(NaiveDateTime, f64)], step_minutes :i64, interpolation :Interpolation, max_gap :Duration) -> Vec<GridPoint> {
+    let (first, last) = match (series.first(), series.last()) {
+        (Some(first), Some(last)) => (first.0, last.0),
+        _ => return Vec::new(),
+    };
+    let step = Duration::minutes(step_minutes);
+    let mut grid = Vec::new();
+    let mut time = first.date().and_hms(first.hour(), 0, 0);
+    while time < first - step / 2 {
+        time += step;
+    }
+    while time <= last + step / 2 {
+        // readings around the grid point
+        let next_index = series.partition_point(|reading| reading.0 < time);
+        let before = if next_index > 0 {series.get(next_index - 1)} else {None};
+        let after = series.get(next_index);
+        let closest = [before, after].iter()
+            .flatten()
+            .filter(|reading| (reading.0 - time).num_seconds().abs() * 2 <= step.num_seconds())
+            .min_by_key(|reading| (reading.0 - time).num_seconds().abs())
+            .map(|reading| reading.1);
+        let point = match (closest, before, after) {
+            (Some(glucose), _, _) => GridPoint {time, glucose: Some(glucose), interpolated: false},
+            (None, Some(before), Some(after)) if interpolates(interpolation, after.0 - before.0, max_gap) => {
+                let share = (time - before.0).num_seconds() as f64 / (after.0 - before.0).num_seconds() as f64;
+                GridPoint {time, glucose: Some(before.1 + (after.1 - before.1) * share), interpolated: true}
+            }
+            _ => GridPoint {time, glucose: None, interpolated: false},
+        };
+        grid.push(point);
+        time += step;
+    }
+    grid
+}
+
+/// The glucose series of the lines on the grid and with the interpolation of the configuration,
+/// without the compression lows if artefacts are to be excluded.
+pub fn regular_series(data :&[FsLibreLine], config :&Config) -> Vec<GridPoint> {
+    let data = artefacts::without_compression_lows(data, config);
+    resample(&analytics::glucose_series(&data), config.grid_minutes, config.interpolation, Duration::minutes(config.max_interpolation_gap))
+}
+
+/// The values of `regular_series`, the one series all glucose statistics are computed from,
+/// so clusters of scans do not weigh more than the historic readings around them.
+pub fn statistics_series(data :&[FsLibreLine], config :&Config) -> Vec<(NaiveDateTime, f64)> {
+    grid_values(&regular_series(data, config))
+}
+
+/// The values of the grid points holding one, interpolated or not.
+pub fn grid_values(grid :&[GridPoint]) -> Vec<(NaiveDateTime, f64)> {
+    grid.iter().filter_map(|point| point.glucose.map(|glucose| (point.time, glucose))).collect()
+}
+
+fn interpolates(interpolation :Interpolation, gap :Duration, max_gap :Duration) -> bool {
+    match interpolation {
+        Interpolation::None => false,
+        Interpolation::Linear => true,
+        Interpolation::LimitedLinear => gap <= max_gap,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hours_minutes :&str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("2021.01.01 {}", hours_minutes), "%Y.%m.%d %H:%M").unwrap()
+    }
+
+    fn assert_near(actual :f64, expected :f64) {
+        assert!((actual - expected).abs() <= 1e-9, "expected {} but was {}", expected, actual);
+    }
+
+    #[test]
+    fn grid_point_takes_the_closest_reading() {
+        let series = [(time("00:00"), 100.0), (time("00:07"), 120.0), (time("00:13"), 140.0)];
+        let grid = resample(&series, 15, Interpolation::None, Duration::zero());
+        assert_eq!(grid.iter().map(|point| point.time).collect::<Vec<NaiveDateTime>>(), vec![time("00:00"), time("00:15")]);
+        assert_near(grid[0].glucose.unwrap(), 100.0);
+        assert_near(grid[1].glucose.unwrap(), 140.0);
+        assert!(grid.iter().all(|point| !point.interpolated));
+    }
+
+    #[test]
+    fn readings_further_than_half_a_step_are_not_taken() {
+        let series = [(time("00:00"), 100.0), (time("00:08"), 120.0)];
+        let grid = resample(&series, 5, Interpolation::None, Duration::zero());
+        // 00:05 lies 5 and 3 minutes from the readings, more than half of the 5 minute step
+        assert!(grid[1].glucose.is_none());
+        assert_near(grid[2].glucose.unwrap(), 120.0);
+        let series = [(time("00:00"), 100.0), (time("00:30"), 160.0)];
+        let grid = resample(&series, 15, Interpolation::None, Duration::zero());
+        assert!(grid[1].glucose.is_none());
+        assert!(!grid[1].interpolated);
+    }
+
+    #[test]
+    fn linear_interpolation_fills_the_gap() {
+        let series = [(time("00:00"), 100.0), (time("01:00"), 160.0)];
+        let grid = resample(&series, 15, Interpolation::Linear, Duration::zero());
+        let values :Vec<f64> = grid.iter().map(|point| point.glucose.unwrap()).collect();
+        for (actual, expected) in values.iter().zip([100.0, 115.0, 130.0, 145.0, 160.0].iter()) {
+            assert_near(*actual, *expected);
+        }
+        let interpolated :Vec<bool> = grid.iter().map(|point| point.interpolated).collect();
+        assert_eq!(interpolated, vec![false, true, true, true, false]);
+    }
+
+    #[test]
+    fn limited_interpolation_stops_at_the_maximum_gap() {
+        let series = [(time("00:00"), 100.0), (time("01:00"), 160.0)];
+        let too_long = resample(&series, 15, Interpolation::LimitedLinear, Duration::minutes(45));
+        assert!(too_long[1..4].iter().all(|point| point.glucose.is_none() && !point.interpolated));
+        let just_fits = resample(&series, 15, Interpolation::LimitedLinear, Duration::minutes(60));
+        assert!(just_fits[1..4].iter().all(|point| point.glucose.is_some() && point.interpolated));
+    }
+
+    #[test]
+    fn grid_values_keep_points_with_glucose() {
+        let series = [(time("00:00"), 100.0), (time("01:00"), 160.0)];
+        let grid = resample(&series, 15, Interpolation::None, Duration::zero());
+        assert_eq!(grid_values(&grid), vec![(time("00:00"), 100.0), (time("01:00"), 160.0)]);
+    }
+
+    #[test]
+    fn statistics_share_the_grid() {
+        // historic readings of 100 for two hours, with a cluster of scans at 300 between two of them
+        let mut data = Vec::new();
+        for index in 0..=8 {
+            let mut line = FsLibreLine::new();
+            line.timestamp = time("00:00") + Duration::minutes(15 * index);
+            line.gluco_hist = 100;
+            data.push(line);
+        }
+        for minute in 36..41 {
+            let mut line = FsLibreLine::new();
+            line.timestamp = time("00:00") + Duration::minutes(minute);
+            line.gluco_scanned = 300;
+            data.push(line);
+        }
+        data.sort_by_key(|line| line.timestamp);
+        let config = Config::new();
+        let series = statistics_series(&data, &config);
+        // every grid point has a historic reading closer than the scans, which weigh nothing
+        assert_eq!(series.len(), 9);
+        assert_near(crate::variability::variability(&data, &config).unwrap().mean, 100.0);
+        assert_near(analytics::glucose_management_indicator(&data, &config).unwrap().mean_glucose, 100.0);
+        assert_near(analytics::time_in_range(&data, &config).unwrap().in_range, 1.0);
+        assert_near(crate::risk::glycemic_risk(&data, &config).unwrap().hbgi, 0.0);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::analytics::{self, TimeInRange};
+use crate::config::Config;
+use crate::model::FsLibreLine;
+use crate::resample;
+
+/// Hypo- and hyperglycemia risk of a period.
+pub struct GlycemicRisk {
+    pub lbgi :f64,
+    pub hbgi :f64,
+    /// Glycemia Risk Index, 0 (best) to 100 (worst), with its hypo and hyper components.
+    pub gri :f64,
+    pub gri_hypo :f64,
+    pub gri_hyper :f64,
+}
+
+/// LBGI and HBGI over `resample::statistics_series`, the GRI from its time in ranges.
+/// Returns `None` if there are no readings.
+pub fn glycemic_risk(data :&[FsLibreLine], config :&Config) -> Option<GlycemicRisk> {
+    let series = resample::statistics_series(data, config);
+    let tir = analytics::time_in_range(data, config)?;
+    if series.is_empty() {
+        return None;
+    }
+    let risks :Vec<f64> = series.iter().map(|reading| risk_value(reading.1)).collect();
+    let count = risks.len() as f64;
+    let (gri_hypo, gri_hyper) = gri_components(&tir);
+    Some(GlycemicRisk {
+        lbgi: risks.iter().filter(|risk| **risk < 0.0).map(|risk| -risk).sum::<f64>() / count,
+        hbgi: risks.iter().filter(|risk| **risk > 0.0).sum::<f64>() / count,
+        gri: (3.0 * gri_hypo + 1.6 * gri_hyper).min(100.0),
+        gri_hypo,
+        gri_hyper,
+    })
+}
+
+/// Kovatchev's symmetrised risk of a reading in mg/dL, negative on the low side:
+/// f = 1.509 * (ln(BG)^1.084 - 5.381), risk = 10 * f^2.
+pub fn risk_value(glucose :f64) -> f64 {
+    let f = 1.509 * (glucose.ln().powf(1.084) - 5.381);
+    10.0 * f * f * f.signum()
+}
+
+/// Hypo component (very low + 0.8 low) and hyper component (very high + 0.5 high)
+/// of the GRI (Klonoff 2023), in percent of time.
+pub fn gri_components(tir :&TimeInRange) -> (f64, f64) {
+    let hypo = (tir.very_low + 0.8 * tir.low) * 100.0;
+    let hyper = (tir.very_high + 0.5 * tir.high) * 100.0;
+    (hypo, hyper)
+}
@@ -0,0 +1,93 @@
+use crate::analytics;
+use crate::model::FsLibreLine;
+
+use chrono::{Duration, NaiveDateTime};
+
+/// Record type of the line logged when a new sensor was started.
+pub const SENSOR_START_TYPE :u32 = 6;
+/// Days a sensor is meant to be worn.
+pub const SENSOR_LIFE_DAYS :i64 = 14;
+/// A sensor ending this much before its life ended failed early.
+pub const EARLY_FAILURE_TOLERANCE_HOURS :i64 = 24;
+/// Without a start record a gap of historic readings this long is taken as a sensor change.
+pub const SESSION_GAP_HOURS :i64 = 12;
+/// A new sensor gives no readings during its first hour.
+pub const WARM_UP_MINUTES :i64 = 60;
+
+/// The time one sensor was worn.
+pub struct Session {
+    /// Time of the start record, or the first reading if there is none.
+    pub start :NaiveDateTime,
+    pub first_reading :NaiveDateTime,
+    pub last_reading :NaiveDateTime,
+    /// Whether a start record began the session, otherwise it was found by a gap
+    /// or began before the data.
+    pub started_by_record :bool,
+    /// Share (0.0 to 1.0) of the session covered by historic readings.
+    pub coverage :f64,
+    /// Whether the sensor ended more than a day before its life ended. Sessions at the
+    /// edges of the data are never taken as failed, they may be cut off by the export.
+    pub early_failure :bool,
+}
+
+impl Session {
+    pub fn wear_duration(&self) -> Duration {
+        self.last_reading - self.start
+    }
+
+    /// Time from the start of the sensor to its first reading.
+    pub fn warm_up(&self) -> Duration {
+        self.first_reading - self.start
+    }
+}
+
+/// Splits the lines into sensor sessions at start records and at long gaps of historic readings.
+pub fn sensor_sessions(data :&[FsLibreLine]) -> Vec<Session> {
+    let session_gap = Duration::hours(SESSION_GAP_HOURS);
+    // start record and lines of every session
+    let mut parts :Vec<(Option<NaiveDateTime>, Vec<FsLibreLine>)> = Vec::new();
+    let mut part_has_hist = false;
+    let mut last_hist :Option<NaiveDateTime> = None;
+    for line in data {
+        let start_record = line.line_type == SENSOR_START_TYPE;
+        let gap = line.gluco_hist > 0 && last_hist.is_some_and(|last_hist| line.timestamp - last_hist > session_gap);
+        // a gap right after a start record is the warm-up of the new sensor, not another session
+        if parts.is_empty() || ((start_record || gap) && part_has_hist) {
+            parts.push((None, Vec::new()));
+            part_has_hist = false;
+        }
+        let part = parts.last_mut().unwrap();
+        if start_record && part.0.is_none() {
+            part.0 = Some(line.timestamp);
+        }
+        part.1.push(line.clone());
+        if line.gluco_hist > 0 {
+            part_has_hist = true;
+            last_hist = Some(line.timestamp);
+        }
+    }
+
+    let part_count = parts.len();
+    parts.into_iter()
+        .enumerate()
+        .filter_map(|(index, (start_record, lines))| {
+            let first_reading = lines.iter().find(|line| line.gluco_hist > 0)?.timestamp;
+            let last_reading = lines.iter().rev().find(|line| line.gluco_hist > 0)?.timestamp;
+            let start = start_record.unwrap_or(first_reading);
+            let covered :i64 = analytics::weighted_hist_readings(&lines).iter().map(|reading| reading.1.num_seconds()).sum();
+            let span = (last_reading - start).num_seconds().max(1);
+            let life_end = start + Duration::days(SENSOR_LIFE_DAYS) - Duration::hours(EARLY_FAILURE_TOLERANCE_HOURS);
+            // the first session may have begun before the data, the last one may still be running
+            let complete = (index > 0 || start_record.is_some()) && index + 1 < part_count;
+            Some(Session {
+                start,
+                first_reading,
+                last_reading,
+                started_by_record: start_record.is_some(),
+                coverage: (covered as f64 / span as f64).min(1.0),
+                early_failure: complete && last_reading < life_end,
+            })
+        })
+        .collect()
+}
+
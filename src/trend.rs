@@ -0,0 +1,77 @@
+use crate::analytics;
+use crate::model::FsLibreLine;
+
+use chrono::{Duration, NaiveDateTime};
+
+/// Readings closer than this to the previous one (e.g. a scan right after a historic reading)
+/// are compared with an earlier reading, so sensor noise does not look like a fast change.
+pub const MIN_INTERVAL_MINUTES :i64 = 5;
+
+/// The trend arrow categories of the FreeStyle Libre reader.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TrendArrow {
+    /// Falling faster than 2 mg/dL per minute.
+    FallingQuickly,
+    /// Falling by 1 to 2 mg/dL per minute.
+    Falling,
+    /// Changing by less than 1 mg/dL per minute.
+    Stable,
+    /// Rising by 1 to 2 mg/dL per minute.
+    Rising,
+    /// Rising faster than 2 mg/dL per minute.
+    RisingQuickly,
+}
+
+impl TrendArrow {
+    pub fn of(rate :f64) -> TrendArrow {
+        if rate < -2.0 {
+            TrendArrow::FallingQuickly
+        } else if rate < -1.0 {
+            TrendArrow::Falling
+        } else if rate <= 1.0 {
+            TrendArrow::Stable
+        } else if rate <= 2.0 {
+            TrendArrow::Rising
+        } else {
+            TrendArrow::RisingQuickly
+        }
+    }
+
+    /// Direction of the arrow in degrees, counter-clockwise from pointing right.
+    pub fn angle(&self) -> f64 {
+        match self {
+            TrendArrow::FallingQuickly => -90.0,
+            TrendArrow::Falling => -45.0,
+            TrendArrow::Stable => 0.0,
+            TrendArrow::Rising => 45.0,
+            TrendArrow::RisingQuickly => 90.0,
+        }
+    }
+}
+
+/// Rate of change in mg/dL per minute at every reading of `analytics::glucose_series`, from the
+/// closest earlier reading at least 5 minutes before. Readings after a gap of more than two
+/// historic intervals have no rate.
+pub fn rate_of_change(data :&[FsLibreLine]) -> Vec<(NaiveDateTime, f64)> {
+    let series = analytics::glucose_series(data);
+    let min_interval = Duration::minutes(MIN_INTERVAL_MINUTES);
+    let max_interval = Duration::minutes(2 * analytics::HIST_INTERVAL_MINUTES);
+    let mut rates = Vec::new();
+    for (index, (time, glucose)) in series.iter().enumerate() {
+        let previous = series[..index].iter()
+            .rev()
+            .find(|reading| *time - reading.0 >= min_interval);
+        if let Some((previous_time, previous_glucose)) = previous {
+            let interval = *time - *previous_time;
+            if interval <= max_interval {
+                rates.push((*time, (glucose - previous_glucose) / (interval.num_seconds() as f64 / 60.0)));
+            }
+        }
+    }
+    rates
+}
+
+/// Trend arrow at every reading with a rate of change.
+pub fn trend_arrows(data :&[FsLibreLine]) -> Vec<(NaiveDateTime, TrendArrow)> {
+    rate_of_change(data).iter().map(|(time, rate)| (*time, TrendArrow::of(*rate))).collect()
+}
@@ -0,0 +1,289 @@
+use crate::analytics;
+use crate::config::Config;
+use crate::model::FsLibreLine;
+use crate::resample;
+
+use chrono::{Duration, NaiveDateTime};
+
+/// Glycemic variability of a glucose series, all values in mg/dL except `cv` (%).
+/// Metrics needing more data than there is (e.g. MODD for a single day) are `None`.
+pub struct Variability {
+    pub mean :f64,
+    pub sd :f64,
+    pub cv :f64,
+    pub mage :Option<f64>,
+    pub conga :Option<f64>,
+    pub modd :Option<f64>,
+    pub j_index :f64,
+}
+
+/// Readings further apart than this from the wanted time are not used as "the value n hours earlier".
+fn match_tolerance() -> Duration {
+    Duration::seconds(analytics::HIST_INTERVAL_MINUTES * 60 / 2)
+}
+
+/// Computes all metrics over `resample::statistics_series`, CONGA with the lag of the configuration.
+/// Returns `None` for less than two values.
+pub fn variability(data :&[FsLibreLine], config :&Config) -> Option<Variability> {
+    let series = resample::statistics_series(data, config);
+    if series.len() < 2 {
+        return None;
+    }
+    let values :Vec<f64> = series.iter().map(|reading| reading.1).collect();
+    let mean = mean(&values);
+    let sd = standard_deviation(&values);
+    Some(Variability {
+        mean,
+        sd,
+        cv: sd / mean * 100.0,
+        mage: mage(&values),
+        conga: conga(&series, config.conga_hours),
+        modd: modd(&series),
+        j_index: j_index(mean, sd),
+    })
+}
+
+pub fn mean(values :&[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Sample standard deviation.
+pub fn standard_deviation(values :&[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    let squares :f64 = values.iter().map(|value| (value - mean) * (value - mean)).sum();
+    (squares / (values.len() - 1) as f64).sqrt()
+}
+
+/// J-index (Wojcicki 1995): 0.001 * (mean + SD)^2 with mg/dL.
+pub fn j_index(mean :f64, sd :f64) -> f64 {
+    0.001 * (mean + sd) * (mean + sd)
+}
+
+/// Mean Amplitude of Glycemic Excursions (Service 1970): the turning points of the series are
+/// the peaks and nadirs it leaves by more than one SD, MAGE is the mean height of the excursions
+/// between them, counted in the direction of the first excursion.
+pub fn mage(values :&[f64]) -> Option<f64> {
+    let sd = standard_deviation(values);
+    if sd == 0.0 {
+        return None;
+    }
+    let mut turning_points :Vec<f64> = Vec::new();
+    let (mut min, mut max) = (values[0], values[0]);
+    // +1 while climbing towards a peak, -1 while falling towards a nadir, 0 until the first excursion
+    let mut direction = 0;
+    for value in values {
+        match direction {
+            0 => {
+                min = min.min(*value);
+                max = max.max(*value);
+                if value - min > sd {
+                    turning_points.push(min);
+                    direction = 1;
+                    max = *value;
+                } else if max - value > sd {
+                    turning_points.push(max);
+                    direction = -1;
+                    min = *value;
+                }
+            }
+            1 => {
+                if *value > max {
+                    max = *value;
+                } else if max - value > sd {
+                    turning_points.push(max);
+                    direction = -1;
+                    min = *value;
+                }
+            }
+            _ => {
+                if *value < min {
+                    min = *value;
+                } else if value - min > sd {
+                    turning_points.push(min);
+                    direction = 1;
+                    max = *value;
+                }
+            }
+        }
+    }
+    // the excursion running at the end of the series is complete once it exceeds one SD
+    match direction {
+        1 if max - turning_points[turning_points.len() - 1] > sd => turning_points.push(max),
+        -1 if turning_points[turning_points.len() - 1] - min > sd => turning_points.push(min),
+        _ => {}
+    }
+    if turning_points.len() < 2 {
+        return None;
+    }
+    let first_rising = turning_points[1] > turning_points[0];
+    let amplitudes :Vec<f64> = turning_points.windows(2)
+        .filter(|excursion| (excursion[1] > excursion[0]) == first_rising)
+        .map(|excursion| (excursion[1] - excursion[0]).abs())
+        .collect();
+    Some(mean(&amplitudes))
+}
+
+/// Continuous Overall Net Glycemic Action (McDonnell 2005): SD of the differences between
+/// every reading and the reading `hours` earlier.
+pub fn conga(series :&[(NaiveDateTime, f64)], hours :i64) -> Option<f64> {
+    let differences = lagged_differences(series, Duration::hours(hours));
+    if differences.len() < 2 {
+        return None;
+    }
+    Some(standard_deviation(&differences))
+}
+
+/// Mean Of Daily Differences (Molnar 1972): mean absolute difference between readings
+/// taken at the same time on two consecutive days.
+pub fn modd(series :&[(NaiveDateTime, f64)]) -> Option<f64> {
+    let differences = lagged_differences(series, Duration::days(1));
+    if differences.is_empty() {
+        return None;
+    }
+    let absolute :Vec<f64> = differences.iter().map(|difference| difference.abs()).collect();
+    Some(mean(&absolute))
+}
+
+fn lagged_differences(series :&[(NaiveDateTime, f64)], lag :Duration) -> Vec<f64> {
+    series.iter()
+        .filter_map(|(time, value)| value_at(series, *time - lag).map(|earlier| value - earlier))
+        .collect()
+}
+
+/// The reading closest to `time`, if it is within the match tolerance.
+pub fn value_at(series :&[(NaiveDateTime, f64)], time :NaiveDateTime) -> Option<f64> {
+    let index = match series.binary_search_by_key(&time, |reading| reading.0) {
+        Ok(index) => return Some(series[index].1),
+        Err(index) => index,
+    };
+    let candidates = [index.checked_sub(1), Some(index)];
+    candidates.iter()
+        .filter_map(|candidate| candidate.and_then(|candidate| series.get(candidate)))
+        .map(|reading| ((reading.0 - time).num_seconds().abs(), reading.1))
+        .filter(|(distance, _)| *distance <= match_tolerance().num_seconds())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(start :&str, values :&[f64]) -> Vec<(NaiveDateTime, f64)> {
+        let start = NaiveDateTime::parse_from_str(start, "%Y.%m.%d %H:%M").unwrap();
+        values.iter().enumerate()
+            .map(|(index, value)| (start + Duration::minutes(15 * index as i64), *value))
+            .collect()
+    }
+
+    fn assert_near(actual :f64, expected :f64, tolerance :f64) {
+        assert!((actual - expected).abs() <= tolerance, "expected {} but was {}", expected, actual);
+    }
+
+    #[test]
+    fn standard_deviation_of_textbook_sample() {
+        // sample SD of 2, 4, 4, 4, 5, 5, 7, 9 is sqrt(32 / 7)
+        assert_near(standard_deviation(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]), (32.0f64 / 7.0).sqrt(), 1e-9);
+    }
+
+    #[test]
+    fn mean_and_standard_deviation_of_nist_numacc1() {
+        // NIST StRD univariate summary statistics, data set NumAcc1: certified mean 10000002, SD 1
+        let values = [10000001.0, 10000003.0, 10000002.0];
+        assert_near(mean(&values), 10000002.0, 1e-9);
+        assert_near(standard_deviation(&values), 1.0, 1e-9);
+    }
+
+    #[test]
+    fn mean_and_standard_deviation_of_nist_numacc3() {
+        // NIST StRD data set NumAcc3: 1000000.2 followed by 500 pairs of 1000000.1 and 1000000.3,
+        // certified mean 1000000.2, SD 0.1
+        let mut values = vec![1000000.2];
+        for _ in 0..500 {
+            values.push(1000000.1);
+            values.push(1000000.3);
+        }
+        assert_near(mean(&values), 1000000.2, 1e-8);
+        assert_near(standard_deviation(&values), 0.1, 1e-8);
+    }
+
+    #[test]
+    fn j_index_reference_values() {
+        // Wojcicki 1995: J = 0.001 * (mean + SD)^2, ideal control 10-20, poor control > 40
+        assert_near(j_index(100.0, 20.0), 14.4, 1e-9);
+        assert_near(j_index(150.0, 50.0), 40.0, 1e-9);
+    }
+
+    #[test]
+    fn mage_of_regular_oscillation_is_its_amplitude() {
+        let values :Vec<f64> = (0..48).map(|index| if index % 8 < 4 {100.0 + 25.0 * (index % 8) as f64} else {200.0 - 25.0 * (index % 8 - 4) as f64}).collect();
+        assert_near(mage(&values).unwrap(), 100.0, 1e-9);
+    }
+
+    #[test]
+    fn mage_ignores_excursions_smaller_than_one_sd() {
+        // the 10 mg/dL wobble on the way up is far below the SD of the series
+        let values = [100.0, 150.0, 140.0, 200.0, 100.0, 200.0, 100.0];
+        assert_near(mage(&values).unwrap(), 100.0, 1e-9);
+    }
+
+    #[test]
+    fn mage_counts_in_the_direction_of_the_first_excursion() {
+        // worked from the definition of Service 1970: the SD of 100, 200, 140, 220, 160 is
+        // sqrt(9120 / 4) = 47.7, all four excursions exceed it; rising first, so MAGE is the mean
+        // of the rises 100 and 80, not of the falls 60 and 60
+        assert_near(mage(&[100.0, 200.0, 140.0, 220.0, 160.0]).unwrap(), 90.0, 1e-9);
+        // the same series mirrored falls first and gives the mean of the falls
+        assert_near(mage(&[220.0, 120.0, 180.0, 100.0, 160.0]).unwrap(), 90.0, 1e-9);
+    }
+
+    #[test]
+    fn mage_of_constant_series_is_undefined() {
+        assert!(mage(&[120.0; 10]).is_none());
+    }
+
+    #[test]
+    fn conga_of_linear_ramp_is_zero() {
+        // every reading is the same 40 mg/dL above the one an hour earlier
+        let values :Vec<f64> = (0..16).map(|index| 100.0 + 10.0 * index as f64).collect();
+        assert_near(conga(&series("2021.01.01 00:00", &values), 1).unwrap(), 0.0, 1e-9);
+    }
+
+    #[test]
+    fn conga_is_the_sample_sd_of_the_differences() {
+        // worked from the definition of McDonnell 2005 (SD of the differences to n hours before):
+        // the readings from 01:00 differ by 10, 30, 0 and 20 from an hour earlier, their sample SD
+        // is sqrt(500 / 3) = 12.9, the population SD would be sqrt(500 / 4) = 11.2
+        let values = [100.0, 100.0, 100.0, 100.0, 110.0, 130.0, 100.0, 120.0];
+        assert_near(conga(&series("2021.01.01 00:00", &values), 1).unwrap(), (500.0f64 / 3.0).sqrt(), 1e-9);
+    }
+
+    #[test]
+    fn conga_needs_readings_an_hour_apart() {
+        assert!(conga(&series("2021.01.01 00:00", &[100.0, 120.0, 140.0]), 1).is_none());
+    }
+
+    #[test]
+    fn modd_of_shifted_day() {
+        let mut values :Vec<f64> = (0..96).map(|index| 100.0 + (index % 12) as f64).collect();
+        values.extend((0..96).map(|index| 130.0 + (index % 12) as f64));
+        assert_near(modd(&series("2021.01.01 00:00", &values)).unwrap(), 30.0, 1e-9);
+    }
+
+    #[test]
+    fn modd_takes_the_absolute_differences() {
+        // worked from the definition of Molnar 1972: the second day is alternately 20 above and
+        // 20 below the first, the mean absolute difference is 20 while the signed ones cancel out
+        let mut values = vec![120.0; 96];
+        values.extend((0..96).map(|index| if index % 2 == 0 {140.0} else {100.0}));
+        assert_near(modd(&series("2021.01.01 00:00", &values)).unwrap(), 20.0, 1e-9);
+    }
+
+    #[test]
+    fn modd_of_single_day_is_undefined() {
+        assert!(modd(&series("2021.01.01 00:00", &[100.0; 96])).is_none());
+    }
+}